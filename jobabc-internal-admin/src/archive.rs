@@ -0,0 +1,288 @@
+// 本地打包：不依赖外部 zip 命令，按文件名排序并固定时间戳，
+// 同一份构建产物总是生成完全相同的压缩包
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum ArchiveFormat {
+    #[default]
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "tar.zst")]
+    TarZst,
+}
+
+const FORMATS: [ArchiveFormat; 3] = [
+    ArchiveFormat::Zip,
+    ArchiveFormat::TarGz,
+    ArchiveFormat::TarZst,
+];
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst",
+        }
+    }
+
+    // 根据文件名后缀判断格式，历史版本可能与当前配置的格式不同
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        FORMATS
+            .into_iter()
+            .find(|f| name.ends_with(&format!(".{}", f.extension())))
+    }
+
+    // 版本号加上压缩包后缀，例如 v1.0.0 -> v1.0.0.tar.gz
+    pub fn file_name(&self, version: &str) -> String {
+        format!("{}.{}", strip_extension(version), self.extension())
+    }
+
+    // 服务器上在当前目录解压的命令
+    pub fn extract_command(&self, archive: &str) -> String {
+        match self {
+            ArchiveFormat::Zip => format!("unzip -oq {}", archive),
+            ArchiveFormat::TarGz => format!("tar -xzf {}", archive),
+            ArchiveFormat::TarZst => format!("tar --zstd -xf {}", archive),
+        }
+    }
+}
+
+// 去掉压缩包后缀
+pub fn strip_extension(name: &str) -> &str {
+    FORMATS
+        .iter()
+        .find_map(|f| name.strip_suffix(&format!(".{}", f.extension())))
+        .unwrap_or(name)
+}
+
+// 校验文件名，与 sha256sum -c 使用的格式一致
+pub fn checksum_file_name(archive: &str) -> String {
+    format!("{}.sha256", archive)
+}
+
+// 计算文件的 SHA-256
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// 在压缩包旁边写入 sha256sum 格式的校验文件，返回校验文件路径
+pub fn write_checksum(archive: &Path) -> Result<PathBuf> {
+    let hash = sha256_file(archive)?;
+    let name = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let checksum = archive.with_file_name(checksum_file_name(&name));
+    fs::write(&checksum, format!("{}  {}\n", hash, name))?;
+    Ok(checksum)
+}
+
+// 一个待打包的条目，路径相对于项目目录
+struct Entry {
+    name: String,
+    path: PathBuf,
+    kind: EntryKind,
+    mode: u32,
+}
+
+enum EntryKind {
+    Dir,
+    File,
+    Symlink(PathBuf),
+}
+
+// 按文件名排序遍历需要打包的目录
+fn collect_entries(project_dir: &Path, dirs: &[&str]) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+
+    for dir in dirs {
+        for entry in WalkDir::new(project_dir.join(dir)).sort_by_file_name() {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(project_dir)?;
+            let name = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let metadata = entry.path().symlink_metadata()?;
+
+            let kind = if metadata.file_type().is_symlink() {
+                EntryKind::Symlink(fs::read_link(entry.path())?)
+            } else if metadata.is_dir() {
+                EntryKind::Dir
+            } else {
+                EntryKind::File
+            };
+
+            entries.push(Entry {
+                name,
+                path: entry.path().to_path_buf(),
+                mode: file_mode(&metadata),
+                kind,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.is_dir() { 0o755 } else { 0o644 }
+}
+
+// 把 dirs 打包成 dest
+pub fn create(project_dir: &Path, dirs: &[&str], dest: &Path, format: ArchiveFormat) -> Result<()> {
+    let entries = collect_entries(project_dir, dirs)?;
+    let file = File::create(dest).with_context(|| format!("无法创建压缩包 {}", dest.display()))?;
+
+    match format {
+        ArchiveFormat::Zip => write_zip(&entries, file),
+        ArchiveFormat::TarGz => {
+            let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            write_tar(&entries, encoder)?.finish()?;
+            Ok(())
+        }
+        ArchiveFormat::TarZst => {
+            let encoder = zstd::Encoder::new(file, 0)?;
+            write_tar(&entries, encoder)?.finish()?;
+            Ok(())
+        }
+    }
+}
+
+fn write_zip(entries: &[Entry], file: File) -> Result<()> {
+    use ::zip::write::SimpleFileOptions;
+    use ::zip::{CompressionMethod, DateTime, ZipWriter};
+
+    let mut writer = ZipWriter::new(file);
+    for entry in entries {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(DateTime::default())
+            .unix_permissions(entry.mode);
+
+        match &entry.kind {
+            EntryKind::Dir => writer.add_directory(format!("{}/", entry.name), options)?,
+            EntryKind::File => {
+                writer.start_file(entry.name.as_str(), options)?;
+                io::copy(&mut File::open(&entry.path)?, &mut writer)?;
+            }
+            EntryKind::Symlink(target) => {
+                writer.add_symlink(entry.name.as_str(), target.to_string_lossy(), options)?
+            }
+        }
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+fn write_tar<W: Write>(entries: &[Entry], writer: W) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(entry.mode);
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+
+        match &entry.kind {
+            EntryKind::Dir => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                builder.append_data(&mut header, format!("{}/", entry.name), io::empty())?;
+            }
+            EntryKind::File => {
+                let file = File::open(&entry.path)?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(file.metadata()?.len());
+                builder.append_data(&mut header, &entry.name, file)?;
+            }
+            EntryKind::Symlink(target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                builder.append_link(&mut header, &entry.name, target)?;
+            }
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    // 在临时目录中生成同样内容的构建产物，文件的写入顺序和修改时间不同
+    fn build_tree(name: &str, reversed: bool, mtime: SystemTime) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("archive-test-{}-{}", std::process::id(), name));
+        fs::remove_dir_all(&root).ok();
+
+        let mut files = vec![
+            ("dist/index.html", "<html></html>"),
+            ("dist/assets/app.js", "console.log(1)"),
+            ("dist/assets/app.css", "body {}"),
+            ("prisma/schema.prisma", "generator client {}"),
+        ];
+        if reversed {
+            files.reverse();
+        }
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(mtime)
+                .unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn same_build_produces_identical_archives() {
+        let first = build_tree("a", false, SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+        let second = build_tree("b", true, SystemTime::now());
+
+        for format in FORMATS {
+            let sha256 = |root: &Path| {
+                let dest = root.join(format.file_name("v1.0.0"));
+                create(root, &["dist", "prisma"], &dest, format).unwrap();
+                sha256_file(&dest).unwrap()
+            };
+            assert_eq!(
+                sha256(&first),
+                sha256(&second),
+                "{} 压缩包不一致",
+                format.extension()
+            );
+        }
+
+        fs::remove_dir_all(&first).ok();
+        fs::remove_dir_all(&second).ok();
+    }
+}
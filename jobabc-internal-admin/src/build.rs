@@ -1,79 +1,121 @@
 use crate::config::Sshconfig;
+use crate::deployer::Deployer;
+use crate::dry_run;
+use crate::progress;
+use crate::ssh;
 use crate::zip;
 use anyhow::Result;
-use std::env;
-use std::path::PathBuf;
 use std::process::Command;
 
 pub fn build_project(
+    deployer: &dyn Deployer,
     version: &str,
     sess: &ssh2::Session,
     is_history: bool,
-    config: &Sshconfig,
 ) -> Result<()> {
     if !is_history {
-        println!("开始构建项目...");
+        // 构建前先确认不会覆盖正在使用的版本
+        zip::check_not_live(sess, deployer.config(), version)?;
 
-        // 进入前端项目目录
-        let home = env::var("HOME")?;
-        let project_dir = PathBuf::from(home).join("jobabc/jobabc-internal-admin");
-        if !project_dir.exists() {
-            anyhow::bail!("项目目录不存在: {}", project_dir.display());
-        }
-        env::set_current_dir(&project_dir)?;
-        println!("当前工作目录: {}", project_dir.display());
+        // 构建可能耗时较长，期间保持 SSH 连接
+        let _keepalive = ssh::keep_alive(sess, &deployer.config().connection);
+        progress::run_plain("检查 git 状态", |_| deployer.prepare())?;
+        progress::run_plain("构建项目", |_| deployer.build())?;
+        println!("构建完成，版本: {}", version);
+    }
 
-        // 检查当前分支
-        let current_branch = Command::new("git")
-            .arg("branch")
-            .arg("--show-current")
-            .output()?;
-        let current_branch = String::from_utf8_lossy(&current_branch.stdout)
-            .trim()
-            .to_string();
+    // 压缩和部署
+    zip::compress_and_deploy(deployer, version, sess, is_history, false)?;
 
-        if current_branch != config.git_checkout {
-            anyhow::bail!(
-                "当前分支 {} 与配置分支 {} 不匹配",
-                current_branch,
-                config.git_checkout
-            );
-        }
+    Ok(())
+}
 
-        // 检查是否有未提交的修改
-        let status = Command::new("git")
-            .arg("status")
-            .arg("--porcelain")
-            .output()?;
-        if !status.stdout.is_empty() {
-            anyhow::bail!("有未提交的修改，请先提交或暂存");
-        }
+pub fn check_git(config: &Sshconfig) -> Result<()> {
+    // 进入项目目录
+    let project_dir = config.project_dir()?;
+    if !project_dir.exists() {
+        anyhow::bail!("项目目录不存在: {}", project_dir.display());
+    }
+    println!("当前工作目录: {}", project_dir.display());
 
-        // 检查是否有未推送的提交
-        let ahead = Command::new("git")
-            .arg("rev-list")
-            .arg("@{u}..HEAD")
-            .output()?;
-        if !ahead.stdout.is_empty() {
-            anyhow::bail!("有未推送的提交，请先推送");
-        }
+    if config.options.dry_run {
+        let dir = project_dir.display();
+        dry_run::print(
+            "检查当前分支",
+            format!("cd {} && git branch --show-current", dir),
+        );
+        dry_run::print(
+            "检查未提交的修改",
+            format!("cd {} && git status --porcelain", dir),
+        );
+        dry_run::print(
+            "检查未推送的提交",
+            format!("cd {} && git rev-list @{{u}}..HEAD", dir),
+        );
+        return Ok(());
+    }
 
-        // 执行构建
-        println!("构建项目...");
-        let status = Command::new("pnpm")
-            .arg("run")
-            .arg(&config.build)
-            .status()?;
+    // 检查当前分支
+    let current_branch = Command::new("git")
+        .arg("branch")
+        .arg("--show-current")
+        .current_dir(&project_dir)
+        .output()?;
+    let current_branch = String::from_utf8_lossy(&current_branch.stdout)
+        .trim()
+        .to_string();
 
-        if !status.success() {
-            anyhow::bail!("构建失败");
-        }
+    if current_branch != config.git_checkout {
+        anyhow::bail!(
+            "当前分支 {} 与配置分支 {} 不匹配",
+            current_branch,
+            config.git_checkout
+        );
+    }
 
-        println!("构建完成，版本: {}", version);
+    // 检查是否有未提交的修改
+    let status = Command::new("git")
+        .arg("status")
+        .arg("--porcelain")
+        .current_dir(&project_dir)
+        .output()?;
+    if !status.stdout.is_empty() {
+        anyhow::bail!("有未提交的修改，请先提交或暂存");
     }
 
-    // 压缩和部署
-    zip::compress_and_deploy(version, sess, is_history, &config)?;
+    // 检查是否有未推送的提交
+    let ahead = Command::new("git")
+        .arg("rev-list")
+        .arg("@{u}..HEAD")
+        .current_dir(&project_dir)
+        .output()?;
+    if !ahead.stdout.is_empty() {
+        anyhow::bail!("有未推送的提交，请先推送");
+    }
+
+    Ok(())
+}
+
+pub fn run_build(config: &Sshconfig) -> Result<()> {
+    // 执行构建
+    if config.options.dry_run {
+        let dir = config.project_dir()?;
+        dry_run::print(
+            "构建",
+            format!("cd {} && pnpm run {}", dir.display(), config.build),
+        );
+        return Ok(());
+    }
+
+    let status = Command::new("pnpm")
+        .arg("run")
+        .arg(&config.build)
+        .current_dir(config.project_dir()?)
+        .status()?;
+
+    if !status.success() {
+        anyhow::bail!("构建失败");
+    }
 
     Ok(())
 }
@@ -1,157 +1,293 @@
-use crate::build;
+use crate::archive::ArchiveFormat;
+use crate::deployer::{self, StaticSite};
 use crate::history;
-use crate::ssh;
+use crate::retention::{self, Retention};
+use crate::ssh::{self, ConnectionSettings};
+use crate::ssh_config;
 use crate::version;
-use prettytable::{Table, format, row};
+use crate::version::VersionChoice;
 use serde::Deserialize;
+use ssh2::Session;
 use std::env;
-use std::io::{self, Write};
 use std::{collections::HashMap, fs, path::PathBuf};
 
 #[derive(Debug, Deserialize)]
 pub struct Sshconfig {
     pub host: String,
-    pub port: u16,
+    // 未配置时使用 ~/.ssh/config 中的 Port，都没有时使用 22
+    #[serde(default)]
+    pub port: Option<u16>,
     pub username: String,
     pub history_path: String,
     pub output_path: String,
     pub build: String,
     pub git_checkout: String,
+    pub local_path: String,
+    #[serde(default)]
+    pub archive_format: ArchiveFormat,
+    #[serde(default)]
+    pub retention: Retention,
+    #[serde(default)]
+    pub connection: ConnectionSettings,
+    // 依次尝试的私钥路径，支持 ~/ 开头；可用 {ENV}_IDENTITY_FILES 环境变量覆盖（逗号分隔）
+    #[serde(default)]
+    pub identity_files: Vec<String>,
+    // 跳板机，格式与 ProxyJump 相同：[user@]host[:port]，host 也可以是 ~/.ssh/config 中的别名。
+    // 可用 {ENV}_JUMP_HOST 环境变量覆盖，未配置时使用 ~/.ssh/config 中的 ProxyJump
+    #[serde(default)]
+    pub jump_host: Option<String>,
+    // 环境名称，由 load_config 填入
+    #[serde(skip)]
+    pub env: String,
+    // 命令行选项，由 load_config 填入
+    #[serde(skip)]
+    pub options: RunOptions,
+}
+
+// 命令行选项，对本次运行的所有操作生效
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOptions {
+    // 演练模式：只打印部署计划，不执行本地命令、上传和远程修改操作
+    pub dry_run: bool,
+    // 严格校验主机密钥：不在 known_hosts 中的服务器直接报错，不提示确认
+    pub strict_host_key_checking: bool,
 }
 
 pub type EnvConfig = HashMap<String, Sshconfig>;
 
-pub fn read_config(env: &str) -> anyhow::Result<()> {
+impl Sshconfig {
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(22)
+    }
+
+    // 本地项目目录，local_path 相对于 HOME
+    pub fn project_dir(&self) -> anyhow::Result<PathBuf> {
+        let home = env::var("HOME")?;
+        Ok(PathBuf::from(home).join(&self.local_path))
+    }
+
+    // 候选私钥路径，未配置时使用 ssh 的默认密钥
+    pub fn identity_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
+        expand_identity_files(&self.identity_files)
+    }
+
+    // 解析跳板机，未配置时返回 None
+    pub fn jump_host(&self) -> anyhow::Result<Option<JumpHost>> {
+        let Some(spec) = self.jump_host.as_deref() else {
+            return Ok(None);
+        };
+        if spec.contains(',') {
+            anyhow::bail!("暂不支持多级跳板机: {}", spec);
+        }
+
+        let (user, host) = match spec.split_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host),
+            None => (None, spec),
+        };
+        let (host, port) = match host.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("跳板机端口无效: {}", spec))?;
+                (host, Some(port))
+            }
+            None => (host, None),
+        };
+
+        // 跳板机也可以是 ~/.ssh/config 中的别名，未指定用户时与目标服务器相同
+        let alias = ssh_config::resolve(host)?;
+        let mut identity_files = alias.identity_files;
+        identity_files.extend(self.identity_files.iter().cloned());
+
+        Ok(Some(JumpHost {
+            host: alias.host_name.unwrap_or_else(|| host.to_string()),
+            port: port.or(alias.port).unwrap_or(22),
+            username: user.or(alias.user).unwrap_or_else(|| self.username.clone()),
+            identity_files,
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JumpHost {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub identity_files: Vec<String>,
+}
+
+impl JumpHost {
+    pub fn identity_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
+        expand_identity_files(&self.identity_files)
+    }
+}
+
+// 与 ssh 客户端一致的默认私钥
+const DEFAULT_IDENTITY_FILES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+// 展开 ~/ 开头的私钥路径，列表为空时使用默认私钥
+fn expand_identity_files(files: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let home = PathBuf::from(env::var("HOME")?);
+    if files.is_empty() {
+        return Ok(DEFAULT_IDENTITY_FILES
+            .iter()
+            .map(|name| home.join(".ssh").join(name))
+            .collect());
+    }
+
+    Ok(files
+        .iter()
+        .map(|path| match path.strip_prefix("~/") {
+            Some(rest) => home.join(rest),
+            None => PathBuf::from(path),
+        })
+        .collect())
+}
+
+// 读取 config.json 中全部环境的配置，不加载 .env、不替换环境变量
+pub fn read_env_config(config_dir: &str) -> anyhow::Result<EnvConfig> {
+    let content = fs::read_to_string(PathBuf::from(config_dir).join("config/config.json"))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+// 加载 .env 和 config.json，返回指定环境的配置
+pub fn load_config(config_dir: &str, env: &str, options: RunOptions) -> anyhow::Result<Sshconfig> {
     // 加载 .env 文件
-    let env_path = PathBuf::from("jobabc-internal-admin/.env");
+    let env_path = PathBuf::from(config_dir).join(".env");
     println!("尝试加载 .env 文件: {}", env_path.display());
     dotenv::from_path(&env_path).ok();
 
-    println!("当前环境: {}", env);
-    println!("环境变量 DEV_HOST: {:?}", env::var("DEV_HOST"));
-    println!("环境变量 DEV_USERNAME: {:?}", env::var("DEV_USERNAME"));
-    println!("环境变量 PROD_HOST: {:?}", env::var("PROD_HOST"));
-    println!("环境变量 PROD_USERNAME: {:?}", env::var("PROD_USERNAME"));
+    let config = read_env_config(config_dir)?;
+    let base_config = config
+        .get(env)
+        .ok_or_else(|| anyhow::anyhow!("没有找到 {} 配置", env))?;
+
+    // 替换环境变量；config.json 中直接写主机名或 ~/.ssh/config 中的别名时不需要环境变量
+    let prefix = env.to_uppercase();
+    let host = env::var(format!("{}_HOST", prefix))
+        .ok()
+        .or_else(|| (!base_config.host.starts_with("${")).then(|| base_config.host.clone()))
+        .ok_or_else(|| anyhow::anyhow!("未找到环境变量 {}_HOST，请检查 .env 文件", prefix))?;
 
-    let content = fs::read_to_string("jobabc-internal-admin/config/config.json")?;
-    let config: EnvConfig = serde_json::from_str(&content)?;
-    let base_config = config.get(env).expect(&format!("没有找到 {} 配置", env));
-
-    // 替换环境变量
-    let host = env::var(format!("{}_HOST", env.to_uppercase())).map_err(|_| {
-        anyhow::anyhow!(
-            "未找到环境变量 {}_HOST，请检查 .env 文件",
-            env.to_uppercase()
-        )
-    })?;
-    let username = env::var(format!("{}_USERNAME", env.to_uppercase())).map_err(|_| {
-        anyhow::anyhow!(
-            "未找到环境变量 {}_USERNAME，请检查 .env 文件",
-            env.to_uppercase()
-        )
-    })?;
-
-    let env_config = Sshconfig {
-        host,
+    // 主机是 ~/.ssh/config 中的别名时，使用其中的 HostName、User、Port 和 IdentityFile
+    let alias = ssh_config::resolve(&host)?;
+    let username = env::var(format!("{}_USERNAME", prefix))
+        .ok()
+        .or_else(|| alias.user.clone())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "未找到环境变量 {}_USERNAME，请检查 .env 文件或 ~/.ssh/config",
+                prefix
+            )
+        })?;
+
+    // .env 中配置了私钥时优先使用，之后再尝试 ~/.ssh/config 中的 IdentityFile
+    let mut identity_files: Vec<String> = match env::var(format!("{}_IDENTITY_FILES", prefix)) {
+        Ok(files) => files
+            .split(',')
+            .map(|file| file.trim().to_string())
+            .filter(|file| !file.is_empty())
+            .collect(),
+        Err(_) => base_config.identity_files.clone(),
+    };
+    identity_files.extend(alias.identity_files);
+
+    Ok(Sshconfig {
+        host: alias.host_name.unwrap_or(host),
         username,
-        port: base_config.port,
+        // config.json 中明确配置的端口优先，避免被 Host * 之类的通配配置覆盖
+        port: base_config.port.or(alias.port),
         history_path: base_config.history_path.clone(),
         output_path: base_config.output_path.clone(),
         build: base_config.build.clone(),
         git_checkout: base_config.git_checkout.clone(),
-    };
+        local_path: base_config.local_path.clone(),
+        archive_format: base_config.archive_format,
+        retention: base_config.retention.clone(),
+        connection: base_config.connection.clone(),
+        identity_files,
+        jump_host: env::var(format!("{}_JUMP_HOST", prefix))
+            .ok()
+            .or_else(|| base_config.jump_host.clone())
+            .or(alias.proxy_jump),
+        env: env.to_string(),
+        options,
+    })
+}
 
-    // 读取 id_rsa
-    let home = env::var("HOME")?;
-    let id_rsa_path = if env == "dev" {
-        PathBuf::from(home).join(".ssh").join("id_rsa")
-    } else {
-        PathBuf::from(home).join("jobabc").join("job123")
-    };
+// 加载配置、连接服务器并打印历史版本
+pub fn connect(
+    config_dir: &str,
+    env: &str,
+    options: RunOptions,
+) -> anyhow::Result<(Sshconfig, Session, Vec<String>)> {
+    connect_with_limit(config_dir, env, history::DEFAULT_DISPLAY_LIMIT, options)
+}
 
-    let id_rsa = fs::read_to_string(&id_rsa_path)?;
+// 与 connect 相同，历史版本表格显示 limit 个版本
+pub fn connect_with_limit(
+    config_dir: &str,
+    env: &str,
+    limit: usize,
+    options: RunOptions,
+) -> anyhow::Result<(Sshconfig, Session, Vec<String>)> {
+    let env_config = load_config(config_dir, env, options)?;
+
+    println!("当前环境: {}", env);
+    println!("环境变量 DEV_HOST: {:?}", env::var("DEV_HOST"));
+    println!("环境变量 DEV_USERNAME: {:?}", env::var("DEV_USERNAME"));
+    println!("环境变量 PROD_HOST: {:?}", env::var("PROD_HOST"));
+    println!("环境变量 PROD_USERNAME: {:?}", env::var("PROD_USERNAME"));
 
     println!("SSH连接配置: {:?}", env_config);
-    let sess = ssh::connect(&env_config, &id_rsa)?;
-    let history_files = history::get_history(&env_config, &sess)?;
-
-    // 创建表格
-    let mut table = Table::new();
-    table.set_format(*format::consts::FORMAT_BOX_CHARS);
-
-    // 添加表头
-    table.add_row(row![
-        "序号",
-        "版本号",
-        "部署时间",
-        "序号",
-        "版本号",
-        "部署时间"
-    ]);
-
-    // 添加数据行，每行显示10个版本
-    for i in 0..10 {
-        let row1 = if i < history_files.len() {
-            format!("{}", i + 1)
-        } else {
-            "".to_string()
-        };
-        let ver1 = if i < history_files.len() {
-            history_files[i].clone()
-        } else {
-            "".to_string()
-        };
+    let sess = ssh::connect(&env_config)?;
+    let history = history::get_history(&env_config, &sess)?;
+    history::print_history(&env_config, &sess, &history, limit)?;
+    let history_files = history.into_iter().map(|entry| entry.name).collect();
 
-        let row2 = if i + 10 < history_files.len() {
-            format!("{}", i + 11)
-        } else {
-            "".to_string()
-        };
-        let ver2 = if i + 10 < history_files.len() {
-            history_files[i + 10].clone()
-        } else {
-            "".to_string()
-        };
+    Ok((env_config, sess, history_files))
+}
 
-        table.add_row(row![row1, ver1, "", row2, ver2, ""]);
-    }
+pub fn read_config(
+    config_dir: &str,
+    env: &str,
+    choice: Option<VersionChoice>,
+    options: RunOptions,
+) -> anyhow::Result<()> {
+    let (env_config, sess, history_files) = connect(config_dir, env, options)?;
 
-    println!("\n历史版本列表:");
-    table.printstd();
-
-    match version::show_version_menu()? {
-        ref s if s == "版本自增" => {
-            if let Some(latest) = version::get_latest_version(&history_files) {
-                let new_version = latest.increment();
-                println!("新版本号: {}", new_version.to_string());
-                build::build_project(&new_version.to_string(), &sess, false, &env_config)?;
-            } else {
-                println!("没有找到历史版本，将创建 v1.0.0.zip");
-                build::build_project("v1.0.0.zip", &sess, false, &env_config)?;
-            }
-        }
-        ref s if s == "指定版本" => {
-            print!("请输入版本号 (格式: v1.0.0): ");
-            io::stdout().flush()?;
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
-            let input = input.trim();
-
-            if version::validate_version(input) {
-                println!("版本号有效: {}", input);
-                build::build_project(&format!("{}.zip", input), &sess, false, &env_config)?;
-            } else {
-                println!("无效的版本号格式");
-            }
-        }
-        ref s if s == "历史版本" => {
-            if let Some(selected) = version::select_history_version(&history_files)? {
-                println!("选择的版本: {}", selected);
-                build::build_project(&selected, &sess, true, &env_config)?;
-            }
-        }
-        _ => unreachable!(),
-    }
+    // 命令行已指定版本时跳过交互菜单
+    let choice = match choice {
+        Some(choice) => choice,
+        None => match version::choose_version(&history_files)? {
+            Some(choice) => choice,
+            None => return Ok(()),
+        },
+    };
+
+    let deployer = StaticSite::new(env_config);
+    deployer::release(&deployer, &sess, &history_files, choice)
+}
+
+// 回滚到上一个部署的版本
+pub fn rollback(
+    config_dir: &str,
+    env: &str,
+    assume_yes: bool,
+    options: RunOptions,
+) -> anyhow::Result<()> {
+    let (env_config, sess, history_files) = connect(config_dir, env, options)?;
+    let deployer = StaticSite::new(env_config);
+    deployer::rollback(&deployer, &sess, &history_files, assume_yes)
+}
 
-    Ok(())
+// 按保留策略清理历史版本
+pub fn prune(
+    config_dir: &str,
+    env: &str,
+    assume_yes: bool,
+    options: RunOptions,
+) -> anyhow::Result<()> {
+    let (env_config, sess, _) = connect(config_dir, env, options)?;
+    retention::prune(&sess, &env_config, assume_yes)
 }
@@ -0,0 +1,167 @@
+use crate::build;
+use crate::config::Sshconfig;
+use crate::git;
+use crate::history;
+use crate::ssh;
+use crate::version::{self, Version, VersionChoice};
+use crate::zip;
+use anyhow::Result;
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use ssh2::Session;
+use std::path::{Path, PathBuf};
+
+// 部署流程：prepare → build → package → upload → activate → post_deploy
+// 各步骤都有默认实现，不同类型的项目只需覆盖有差异的步骤
+pub trait Deployer {
+    fn config(&self) -> &Sshconfig;
+
+    // 需要打包上传的目录，相对于项目目录
+    fn package_dirs(&self) -> &[&str] {
+        &["dist"]
+    }
+
+    // 构建前检查：分支、未提交和未推送的修改
+    fn prepare(&self) -> Result<()> {
+        build::check_git(self.config())
+    }
+
+    fn build(&self) -> Result<()> {
+        build::run_build(self.config())
+    }
+
+    // 压缩打包目录，返回本地压缩包路径
+    fn package(&self, version: &str) -> Result<PathBuf> {
+        zip::package(self.config(), version, self.package_dirs())
+    }
+
+    // 上传到历史版本目录
+    fn upload(&self, sess: &mut Session, version: &str, archive: &Path) -> Result<()> {
+        zip::upload(sess, self.config(), version, archive)
+    }
+
+    // 从历史版本解压到发布目录并切换 current
+    fn activate(&self, sess: &Session, version: &str, is_history: bool) -> Result<()> {
+        zip::activate(
+            sess,
+            self.config(),
+            version,
+            self.package_dirs(),
+            is_history,
+        )
+    }
+
+    // 部署完成后的收尾工作，例如重启服务
+    fn post_deploy(&self, sess: &Session) -> Result<()>;
+}
+
+// 按选择的版本方式执行部署
+pub fn release(
+    deployer: &dyn Deployer,
+    sess: &Session,
+    history_files: &[String],
+    choice: VersionChoice,
+) -> Result<()> {
+    let format = deployer.config().archive_format;
+    match choice {
+        VersionChoice::Increment(kind) => {
+            if let Some(latest) = version::get_latest_version(history_files) {
+                let new_version = format.file_name(&latest.bump(kind).to_string());
+                println!("新版本号: {}", new_version);
+                build::build_project(deployer, &new_version, sess, false)?;
+            } else {
+                let new_version = format.file_name("v1.0.0");
+                println!("没有找到历史版本，将创建 {}", new_version);
+                build::build_project(deployer, &new_version, sess, false)?;
+            }
+        }
+        VersionChoice::Specified(input) => {
+            build::build_project(deployer, &format.file_name(&input), sess, false)?;
+        }
+        VersionChoice::History(selected) => {
+            let selected = version::find_history_version(history_files, &selected)
+                .ok_or_else(|| anyhow::anyhow!("历史版本 {} 不存在", selected))?;
+            build::build_project(deployer, &selected, sess, true)?;
+        }
+        VersionChoice::Git => {
+            let next = git::next_version(deployer.config())?;
+            let new_version = format.file_name(&next.to_string());
+            println!("新版本号: {}", new_version);
+            build::build_project(deployer, &new_version, sess, false)?;
+            git::tag_and_push(deployer.config(), &next)?;
+        }
+    }
+
+    Ok(())
+}
+
+// 回滚到部署记录中的上一个版本；记录中的版本都已被清理时选择比当前版本旧的最新历史版本。
+// 服务器上没有当前版本记录时（本工具记录部署之前的服务器），把最新的历史版本当作当前版本
+pub fn rollback(
+    deployer: &dyn Deployer,
+    sess: &Session,
+    history_files: &[String],
+    assume_yes: bool,
+) -> Result<()> {
+    let config = deployer.config();
+    let current = match history::current_version(config, sess)? {
+        Some(current) => Some(current),
+        None => {
+            let latest = version::get_latest_version(history_files)
+                .and_then(|v| version::find_history_version(history_files, &v.to_string()));
+            if let Some(latest) = &latest {
+                println!("服务器上没有当前版本记录，按最新的历史版本 {} 处理", latest);
+            }
+            latest
+        }
+    };
+    let target =
+        history::select_rollback_target(&history::deploy_log(config, sess)?, history_files)
+            .or_else(|| {
+                // 不知道当前版本时不能回滚，否则会重新部署正在运行的最新版本
+                let current = current.as_deref()?.parse::<Version>().ok()?;
+                version::get_latest_version_before(history_files, Some(&current))
+                    .and_then(|v| version::find_history_version(history_files, &v.to_string()))
+            });
+    let Some(target) = target else {
+        anyhow::bail!("没有可以回滚的历史版本");
+    };
+
+    println!(
+        "当前版本: {}，将回滚到: {}",
+        current.as_deref().unwrap_or("未知"),
+        target
+    );
+    if !assume_yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("确认回滚到 {}？", target))
+            .default(false)
+            .interact()?
+    {
+        println!("已取消回滚");
+        return Ok(());
+    }
+
+    zip::compress_and_deploy(deployer, &target, sess, true, true)
+}
+
+// 静态站点：解压后重新加载 nginx
+pub struct StaticSite {
+    config: Sshconfig,
+}
+
+impl StaticSite {
+    pub fn new(config: Sshconfig) -> Self {
+        StaticSite { config }
+    }
+}
+
+impl Deployer for StaticSite {
+    fn config(&self) -> &Sshconfig {
+        &self.config
+    }
+
+    fn post_deploy(&self, sess: &Session) -> Result<()> {
+        ssh::run_step(sess, &self.config, "重启 nginx", "nginx -s reload")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+// 演练模式：只打印部署计划，不执行本地命令、上传和远程修改操作。
+// 是否开启由 RunOptions::dry_run 决定，随 Sshconfig 传递
+use std::fmt::Display;
+
+// 打印本应执行的步骤
+pub fn print(step: &str, detail: impl Display) {
+    println!("[dry-run] {}: {}", step, detail);
+}
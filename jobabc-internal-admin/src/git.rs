@@ -0,0 +1,200 @@
+// 根据 git 标签和约定式提交（Conventional Commits）计算版本号，部署成功后打标签
+use crate::config::Sshconfig;
+use crate::dry_run;
+use crate::version::{BumpKind, Version};
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+// 在项目目录执行 git 命令，失败时返回空字符串
+pub fn output(project_dir: &Path, args: &[&str]) -> String {
+    Command::new("git")
+        .args(args)
+        .current_dir(project_dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+// 在项目目录执行 git 命令，失败时带上 stderr 返回错误
+fn run(config: &Sshconfig, project_dir: &Path, step: &str, args: &[&str]) -> Result<()> {
+    if config.options.dry_run {
+        dry_run::print(
+            step,
+            format!("cd {} && git {}", project_dir.display(), args.join(" ")),
+        );
+        return Ok(());
+    }
+
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(project_dir)
+        .output()
+        .with_context(|| format!("{}失败", step))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{}失败: {}",
+            step,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+// 列出可以解析为版本号的标签
+fn version_tags(project_dir: &Path, args: &[&str]) -> Vec<(String, Version)> {
+    output(project_dir, args)
+        .lines()
+        .filter_map(|tag| tag.parse::<Version>().ok().map(|v| (tag.to_string(), v)))
+        .collect()
+}
+
+// 当前提交之前（含当前提交）最新的版本标签
+pub fn latest_tag(project_dir: &Path) -> Option<(String, Version)> {
+    version_tags(project_dir, &["tag", "--list", "v*", "--merged", "HEAD"])
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.cmp(b))
+}
+
+// 指向当前提交的版本标签，例如 dev 已经部署并打过标签，prod 部署同一个提交
+fn head_tag(project_dir: &Path) -> Option<Version> {
+    version_tags(project_dir, &["tag", "--list", "v*", "--points-at", "HEAD"])
+        .into_iter()
+        .map(|(_, v)| v)
+        .max()
+}
+
+// 自 tag 以来的提交说明
+fn commit_messages(project_dir: &Path, tag: &str) -> Vec<String> {
+    let range = format!("{}..HEAD", tag);
+    output(project_dir, &["log", "--format=%B%x00", &range])
+        .split('\0')
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty())
+        .collect()
+}
+
+// 按约定式提交确定自增方式：破坏性变更升级主版本，feat 升级次版本，其余升级修订号
+pub fn bump_kind(messages: &[String]) -> BumpKind {
+    let mut kind = BumpKind::Patch;
+    for message in messages {
+        let subject = message.lines().next().unwrap_or_default();
+        let prefix = subject.split(':').next().unwrap_or_default();
+        let breaking = (subject.contains(':') && prefix.ends_with('!'))
+            || message.contains("BREAKING CHANGE:")
+            || message.contains("BREAKING-CHANGE:");
+        if breaking {
+            return BumpKind::Major;
+        }
+
+        let commit_type = prefix.split('(').next().unwrap_or_default().trim();
+        if commit_type == "feat" {
+            kind = BumpKind::Minor;
+        }
+    }
+    kind
+}
+
+// 计算下一个版本号：当前提交已有版本标签时直接沿用，
+// 否则在最新标签的基础上按提交说明自增，没有标签时从 v1.0.0 开始
+pub fn next_version(config: &Sshconfig) -> Result<Version> {
+    let project_dir = config.project_dir()?;
+    run(
+        config,
+        &project_dir,
+        "获取远程标签",
+        &["fetch", "--tags", "--quiet"],
+    )?;
+
+    if let Some(version) = head_tag(&project_dir) {
+        println!("当前提交已有标签 {}，沿用该版本", version);
+        return Ok(version);
+    }
+
+    let Some((tag, latest)) = latest_tag(&project_dir) else {
+        println!("没有找到版本标签，将从 v1.0.0 开始");
+        return Ok(Version::new(1, 0, 0));
+    };
+
+    let messages = commit_messages(&project_dir, &tag);
+    let kind = bump_kind(&messages);
+    let next = latest.bump(kind);
+    println!(
+        "最新标签 {}，之后有 {} 个提交，按 {} 自增为 {}",
+        tag,
+        messages.len(),
+        kind.as_str(),
+        next
+    );
+    Ok(next)
+}
+
+// 部署成功后创建并推送版本标签，标签已存在时跳过
+pub fn tag_and_push(config: &Sshconfig, version: &Version) -> Result<()> {
+    let project_dir = config.project_dir()?;
+    let tag = version.to_string();
+    if !output(&project_dir, &["tag", "--list", &tag]).is_empty() {
+        println!("标签 {} 已存在，跳过打标签", tag);
+        return Ok(());
+    }
+
+    let message = format!("部署 {} 到 {}", tag, config.env);
+    run(
+        config,
+        &project_dir,
+        "创建标签",
+        &["tag", "-a", &tag, "-m", &message],
+    )?;
+    run(config, &project_dir, "推送标签", &["push", "origin", &tag])?;
+    println!("已创建并推送标签 {}", tag);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(messages: &[&str]) -> BumpKind {
+        bump_kind(&messages.iter().map(|m| m.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn fixes_bump_patch() {
+        assert_eq!(kind(&[]), BumpKind::Patch);
+        assert_eq!(kind(&["fix: 修复登录", "chore: 更新依赖"]), BumpKind::Patch);
+    }
+
+    #[test]
+    fn feat_bumps_minor() {
+        assert_eq!(kind(&["fix: 修复登录", "feat: 新增导出"]), BumpKind::Minor);
+        assert_eq!(kind(&["feat(user): 新增头像"]), BumpKind::Minor);
+        // 正文中出现 feat 不算
+        assert_eq!(kind(&["fix: 修复 feat 开关"]), BumpKind::Patch);
+    }
+
+    #[test]
+    fn bang_bumps_major() {
+        assert_eq!(kind(&["fix!: 修改接口返回值"]), BumpKind::Major);
+        assert_eq!(kind(&["feat(api)!: 删除旧接口"]), BumpKind::Major);
+        // 没有冒号的标题不是约定式提交
+        assert_eq!(kind(&["Revert!"]), BumpKind::Patch);
+    }
+
+    #[test]
+    fn breaking_change_footer_bumps_major() {
+        assert_eq!(
+            kind(&[
+                "feat: 新增导出",
+                "fix: 调整配置\n\nBREAKING CHANGE: 配置项改名"
+            ]),
+            BumpKind::Major
+        );
+        assert_eq!(
+            kind(&["refactor: 拆分模块\n\nBREAKING-CHANGE: 移除旧模块"]),
+            BumpKind::Major
+        );
+    }
+}
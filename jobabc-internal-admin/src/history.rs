@@ -1,17 +1,350 @@
+use crate::archive::ArchiveFormat;
 use crate::config::Sshconfig;
-use crate::ssh;
+use crate::dry_run;
+use crate::metadata;
+use crate::version::{self, Version};
 use anyhow::Result;
-use ssh2::Session;
+use prettytable::{Cell, Row, Table, format, row};
+use ssh2::{ErrorCode, Session, Sftp};
+use std::io::{Read, Write};
+use std::path::Path;
 
-// 获取历史记录
-pub fn get_history(config: &Sshconfig, sess: &Session) -> Result<Vec<String>> {
-    let output = ssh::execute_command(sess, &format!("cd {} && ls -l", config.history_path))?;
-    let history = output.split("\n").collect::<Vec<&str>>();
-    let history_files = history
+// 历史版本目录中的一个压缩包
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub name: String,
+    pub size: u64,
+    pub mtime: u64,
+}
+
+// SFTP 返回的“文件不存在”错误码
+const SFTP_NO_SUCH_FILE: i32 = 2;
+
+// 获取历史记录：通过 SFTP 读取目录，只保留版本压缩包，按版本号从新到旧排序
+pub fn get_history(config: &Sshconfig, sess: &Session) -> Result<Vec<HistoryEntry>> {
+    let sftp = sess.sftp()?;
+    let files = match sftp.readdir(Path::new(&config.history_path)) {
+        Ok(files) => files,
+        // 第一次部署时目录还不存在
+        Err(e) if e.code() == ErrorCode::SFTP(SFTP_NO_SUCH_FILE) => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut history = files
+        .into_iter()
+        .filter(|(_, stat)| stat.is_file())
+        .filter_map(|(path, stat)| {
+            let name = path.file_name()?.to_str()?.to_string();
+            // 过滤校验文件、元数据和未完成的上传
+            let version = archive_version(&name)?;
+            Some((
+                version,
+                HistoryEntry {
+                    name,
+                    size: stat.size.unwrap_or(0),
+                    mtime: stat.mtime.unwrap_or(0),
+                },
+            ))
+        })
+        .collect::<Vec<_>>();
+    history.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    Ok(history.into_iter().map(|(_, entry)| entry).collect())
+}
+
+// 历史版本目录中的压缩包：版本号加上支持的压缩包后缀，例如 v1.2.3.zip。
+// 没有后缀的文件无法解压，不算作历史版本
+fn archive_version(name: &str) -> Option<Version> {
+    ArchiveFormat::from_file_name(name)?;
+    name.parse().ok()
+}
+
+// 部署记录：每行一个版本，按部署顺序排列。回滚时截掉被回滚的版本，
+// 再次回滚会继续往前，而不是在两个版本之间来回切换
+const DEPLOY_LOG: &str = ".deploys";
+
+// 部署记录最多保留的条数
+const DEPLOY_LOG_LIMIT: usize = 100;
+
+// 通过 SFTP 读取 history_path 中的小文件，文件不存在时返回 None
+fn read_file(config: &Sshconfig, sftp: &Sftp, name: &str) -> Result<Option<String>> {
+    let path = Path::new(&config.history_path).join(name);
+    let mut file = match sftp.open(&path) {
+        Ok(file) => file,
+        Err(e) if e.code() == ErrorCode::SFTP(SFTP_NO_SUCH_FILE) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(Some(content))
+}
+
+fn write_file(config: &Sshconfig, sftp: &Sftp, name: &str, content: &str) -> Result<()> {
+    let path = Path::new(&config.history_path).join(name);
+    let mut file = sftp.create(&path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+// 读取服务器上记录的当前版本和上一个版本，文件不存在时返回 None
+fn read_marker(config: &Sshconfig, sess: &Session, name: &str) -> Result<Option<String>> {
+    let content = read_file(config, &sess.sftp()?, name)?.unwrap_or_default();
+    let version = content.trim();
+    Ok((!version.is_empty()).then(|| version.to_string()))
+}
+
+pub fn current_version(config: &Sshconfig, sess: &Session) -> Result<Option<String>> {
+    read_marker(config, sess, ".current")
+}
+
+pub fn previous_version(config: &Sshconfig, sess: &Session) -> Result<Option<String>> {
+    read_marker(config, sess, ".previous")
+}
+
+// 读取部署记录；早期部署没有记录文件，用 .previous 和 .current 代替
+pub fn deploy_log(config: &Sshconfig, sess: &Session) -> Result<Vec<String>> {
+    if let Some(content) = read_file(config, &sess.sftp()?, DEPLOY_LOG)? {
+        return Ok(content
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect());
+    }
+
+    Ok([
+        previous_version(config, sess)?,
+        current_version(config, sess)?,
+    ]
+    .into_iter()
+    .flatten()
+    .collect())
+}
+
+// 更新部署记录：回滚到记录中的版本时截掉它之后的版本，否则追加到末尾
+pub fn update_deploy_log(mut log: Vec<String>, version: &str, rollback: bool) -> Vec<String> {
+    if rollback && let Some(i) = log.iter().rposition(|v| v == version) {
+        log.truncate(i + 1);
+        return log;
+    }
+    if log.last().map(String::as_str) != Some(version) {
+        log.push(version.to_string());
+    }
+    let excess = log.len().saturating_sub(DEPLOY_LOG_LIMIT);
+    log.drain(..excess);
+    log
+}
+
+// 回滚目标：部署记录中当前版本之前、压缩包仍然存在的最近一个版本
+pub fn select_rollback_target(log: &[String], history: &[String]) -> Option<String> {
+    let (current, earlier) = log.split_last()?;
+    earlier
         .iter()
-        .filter(|s| !s.is_empty()) // 过滤空行
-        .map(|s| s.split_whitespace().nth(8).unwrap_or("").to_string())
-        .filter(|s| !s.is_empty()) // 过滤空文件名
-        .collect::<Vec<String>>();
-    Ok(history_files)
+        .rev()
+        .filter(|v| *v != current)
+        .find_map(|v| version::find_history_version(history, v))
+}
+
+// 部署成功后更新部署记录，并写入当前版本和上一个版本
+pub fn record_deploy(
+    config: &Sshconfig,
+    sess: &Session,
+    version: &str,
+    rollback: bool,
+) -> Result<()> {
+    let log = update_deploy_log(deploy_log(config, sess)?, version, rollback);
+    let previous = log.len().checked_sub(2).map(|i| log[i].as_str());
+
+    if config.options.dry_run {
+        dry_run::print("记录当前版本", version);
+        if let Some(previous) = previous {
+            dry_run::print("记录上一个版本", previous);
+        }
+        return Ok(());
+    }
+
+    let sftp = sess.sftp()?;
+    let mut content = log.join("\n");
+    content.push('\n');
+    write_file(config, &sftp, DEPLOY_LOG, &content)?;
+    if let Some(previous) = previous {
+        write_file(config, &sftp, ".previous", previous)?;
+    }
+    write_file(config, &sftp, ".current", version)?;
+
+    Ok(())
+}
+
+// 历史版本表格默认显示的数量
+pub const DEFAULT_DISPLAY_LIMIT: usize = 20;
+
+// 以表格形式打印最新的 limit 个历史版本及其部署信息，当前部署的版本高亮显示
+pub fn print_history(
+    config: &Sshconfig,
+    sess: &Session,
+    history: &[HistoryEntry],
+    limit: usize,
+) -> Result<()> {
+    // 创建表格
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BOX_CHARS);
+
+    // 添加表头
+    table.add_row(row![
+        "序号",
+        "版本号",
+        "部署时间",
+        "环境",
+        "分支",
+        "提交",
+        "构建人",
+        "构建时间",
+        "大小",
+        "SHA-256"
+    ]);
+
+    let current = current_version(config, sess)?;
+    let sftp = sess.sftp()?;
+    for (i, entry) in history.iter().take(limit).enumerate() {
+        let metadata = metadata::load(&sftp, config, &entry.name)?.unwrap_or_default();
+        // 没有元数据的旧版本用文件修改时间代替部署时间
+        let deploy_time = if metadata.deploy_time.is_empty() {
+            format_time(entry.mtime)
+        } else {
+            metadata.deploy_time
+        };
+        let is_current = current.as_deref() == Some(entry.name.as_str());
+        let index = if is_current {
+            format!("▶ {}", i + 1)
+        } else {
+            format!("{}", i + 1)
+        };
+        let cells = [
+            index,
+            entry.name.clone(),
+            deploy_time,
+            metadata.environment,
+            metadata.git_branch,
+            short(&metadata.git_commit, 8).to_string(),
+            metadata.builder,
+            metadata.build_time,
+            format_size(entry.size),
+            short(&metadata.sha256, 12).to_string(),
+        ];
+        table.add_row(Row::new(
+            cells
+                .iter()
+                .map(|c| {
+                    let cell = Cell::new(c);
+                    if is_current {
+                        cell.style_spec("Fgb")
+                    } else {
+                        cell
+                    }
+                })
+                .collect(),
+        ));
+    }
+
+    println!("\n历史版本列表（▶ 为当前部署的版本）:");
+    table.printstd();
+    if history.len() > limit {
+        println!(
+            "共 {} 个版本，仅显示最新的 {} 个，可使用 --limit 调整",
+            history.len(),
+            limit
+        );
+    }
+
+    Ok(())
+}
+
+fn short(s: &str, len: usize) -> &str {
+    s.get(..len).unwrap_or(s)
+}
+
+fn format_time(mtime: u64) -> String {
+    chrono::DateTime::from_timestamp(mtime as i64, 0)
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+fn format_size(size: u64) -> String {
+    if size == 0 {
+        return String::new();
+    }
+    format!("{:.1} MB", size as f64 / 1024.0 / 1024.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(versions: &[&str]) -> Vec<String> {
+        versions.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn only_version_archives_are_history() {
+        assert!(archive_version("v1.2.3.zip").is_some());
+        assert!(archive_version("v1.4.0-rc.1.tar.gz").is_some());
+        assert!(archive_version("v1.2.3.tar.zst").is_some());
+        assert!(archive_version("v1.2.3").is_none());
+        assert!(archive_version("v1.2.3.zip.sha256").is_none());
+        assert!(archive_version("v1.2.3.zip.part").is_none());
+        assert!(archive_version("latest.zip").is_none());
+    }
+
+    #[test]
+    fn deploy_appends_to_log() {
+        let updated = update_deploy_log(log(&["v1.0.0.zip", "v1.0.1.zip"]), "v1.0.2.zip", false);
+        assert_eq!(updated, log(&["v1.0.0.zip", "v1.0.1.zip", "v1.0.2.zip"]));
+    }
+
+    #[test]
+    fn redeploying_current_does_not_duplicate() {
+        let updated = update_deploy_log(log(&["v1.0.0.zip"]), "v1.0.0.zip", false);
+        assert_eq!(updated, log(&["v1.0.0.zip"]));
+    }
+
+    #[test]
+    fn repeated_rollbacks_keep_going_back() {
+        let history = log(&["v1.0.2.zip", "v1.0.1.zip", "v1.0.0.zip"]);
+        let mut deploys = log(&["v1.0.0.zip", "v1.0.1.zip", "v1.0.2.zip"]);
+
+        let target = select_rollback_target(&deploys, &history).unwrap();
+        assert_eq!(target, "v1.0.1.zip");
+        deploys = update_deploy_log(deploys, &target, true);
+
+        let target = select_rollback_target(&deploys, &history).unwrap();
+        assert_eq!(target, "v1.0.0.zip");
+        deploys = update_deploy_log(deploys, &target, true);
+
+        assert_eq!(deploys, log(&["v1.0.0.zip"]));
+        assert_eq!(select_rollback_target(&deploys, &history), None);
+    }
+
+    #[test]
+    fn rollback_skips_pruned_archives() {
+        let history = log(&["v1.0.2.zip", "v1.0.0.zip"]);
+        let deploys = log(&["v1.0.0.zip", "v1.0.1.zip", "v1.0.2.zip"]);
+        assert_eq!(
+            select_rollback_target(&deploys, &history).as_deref(),
+            Some("v1.0.0.zip")
+        );
+    }
+
+    #[test]
+    fn log_is_capped() {
+        let deploys = (0..DEPLOY_LOG_LIMIT)
+            .map(|i| format!("v1.0.{}.zip", i))
+            .collect();
+        let updated = update_deploy_log(deploys, "v2.0.0.zip", false);
+        assert_eq!(updated.len(), DEPLOY_LOG_LIMIT);
+        assert_eq!(updated.first().map(String::as_str), Some("v1.0.1.zip"));
+        assert_eq!(updated.last().map(String::as_str), Some("v2.0.0.zip"));
+    }
 }
@@ -0,0 +1,118 @@
+// 主机密钥校验：握手后与 ~/.ssh/known_hosts 比对，防止把构建产物上传到被冒充的服务器
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use ssh2::{CheckResult, HashType, HostKeyType, KnownHostFileKind, Session};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+fn known_hosts_path() -> Result<PathBuf> {
+    Ok(PathBuf::from(env::var("HOME")?)
+        .join(".ssh")
+        .join("known_hosts"))
+}
+
+// known_hosts 中的主机名，非 22 端口写成 [host]:port
+fn host_entry(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+fn key_type_name(key_type: HostKeyType) -> &'static str {
+    match key_type {
+        HostKeyType::Rsa => "ssh-rsa",
+        HostKeyType::Dss => "ssh-dss",
+        HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+        HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+        HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+        HostKeyType::Ed25519 => "ssh-ed25519",
+        HostKeyType::Unknown => "unknown",
+    }
+}
+
+// 与 ssh-keygen -l 相同的 SHA256 指纹
+fn fingerprint(sess: &Session) -> String {
+    sess.host_key_hash(HashType::Sha256)
+        .map(|hash| format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+        .unwrap_or_default()
+}
+
+// 校验服务器的主机密钥：一致时通过，不一致时报错并显示指纹；
+// 未记录的主机在交互模式下确认后写入 known_hosts，严格模式下直接报错。
+// 非交互运行（例如 cron / CI）时无法确认，同样按严格模式处理
+pub fn verify(sess: &Session, host: &str, port: u16, strict: bool) -> Result<()> {
+    let (key, key_type) = sess
+        .host_key()
+        .ok_or_else(|| anyhow::anyhow!("无法获取服务器 {} 的主机密钥", host))?;
+    let path = known_hosts_path()?;
+
+    let mut known_hosts = sess.known_hosts()?;
+    if let Ok(content) = fs::read_to_string(&path) {
+        for line in content.lines() {
+            // 跳过 libssh2 无法解析的行，例如 @cert-authority 和不支持的密钥类型
+            known_hosts.read_str(line, KnownHostFileKind::OpenSSH).ok();
+        }
+    }
+
+    let entry = host_entry(host, port);
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => anyhow::bail!(
+            "服务器 {} 的主机密钥与 {} 中的记录不一致，可能遭到中间人攻击！\n\
+             服务器提供的 {} 密钥指纹: {}\n\
+             如果确认服务器已更换密钥，请先执行 ssh-keygen -R '{}' 删除旧记录",
+            entry,
+            path.display(),
+            key_type_name(key_type),
+            fingerprint(sess),
+            entry
+        ),
+        CheckResult::NotFound => {
+            println!(
+                "服务器 {} 不在 known_hosts 中，{} 密钥指纹: {}",
+                entry,
+                key_type_name(key_type),
+                fingerprint(sess)
+            );
+            if strict || !io::stdin().is_terminal() {
+                anyhow::bail!(
+                    "严格模式下不信任未知主机 {}，请先手动执行 ssh 连接确认指纹",
+                    entry
+                );
+            }
+            if !Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("确认指纹无误并信任该主机？")
+                .default(false)
+                .interact()?
+            {
+                anyhow::bail!("未信任主机 {}，已取消连接", entry);
+            }
+
+            // 追加一行，不改写文件中已有的记录
+            let line = format!(
+                "{} {} {}\n",
+                entry,
+                key_type_name(key_type),
+                STANDARD.encode(key)
+            );
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(line.as_bytes()))
+                .with_context(|| format!("无法写入 {}", path.display()))?;
+            println!("已将 {} 加入 {}", entry, path.display());
+            Ok(())
+        }
+        CheckResult::Failure => anyhow::bail!("校验服务器 {} 的主机密钥失败", entry),
+    }
+}
@@ -1,6 +1,15 @@
+pub mod archive;
 pub mod build;
 pub mod config;
+pub mod deployer;
+pub mod dry_run;
+pub mod git;
 pub mod history;
+pub mod known_hosts;
+pub mod metadata;
+pub mod progress;
+pub mod retention;
 pub mod ssh;
+pub mod ssh_config;
 pub mod version;
 pub mod zip;
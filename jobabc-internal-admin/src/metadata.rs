@@ -0,0 +1,108 @@
+// 部署元数据：每个版本在 history_path 中都有一个 <压缩包>.json，
+// 记录构建来源和最近一次部署的信息
+use crate::archive;
+use crate::config::Sshconfig;
+use crate::dry_run;
+use crate::git;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use ssh2::{Session, Sftp};
+use std::env;
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeployMetadata {
+    pub version: String,
+    pub git_commit: String,
+    pub git_branch: String,
+    pub builder: String,
+    pub build_time: String,
+    pub deploy_time: String,
+    pub environment: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+// 元数据文件名
+pub fn metadata_file_name(archive: &str) -> String {
+    format!("{}.json", archive)
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+impl DeployMetadata {
+    // 打包完成后收集构建信息
+    pub fn collect(config: &Sshconfig, version: &str, archive_file: &Path) -> Result<Self> {
+        if config.options.dry_run {
+            dry_run::print("收集构建信息", "git rev-parse HEAD、git config user.name");
+            return Ok(DeployMetadata {
+                version: version.to_string(),
+                environment: config.env.clone(),
+                ..Default::default()
+            });
+        }
+
+        let project_dir = config.project_dir()?;
+        let builder = match git::output(&project_dir, &["config", "user.name"]) {
+            name if name.is_empty() => env::var("USER").unwrap_or_default(),
+            name => name,
+        };
+
+        Ok(DeployMetadata {
+            version: version.to_string(),
+            git_commit: git::output(&project_dir, &["rev-parse", "HEAD"]),
+            git_branch: git::output(&project_dir, &["branch", "--show-current"]),
+            builder,
+            build_time: now(),
+            deploy_time: String::new(),
+            environment: config.env.clone(),
+            size: archive_file.metadata()?.len(),
+            sha256: archive::sha256_file(archive_file)?,
+        })
+    }
+}
+
+// 读取服务器上的元数据，不存在或无法解析时返回 None
+pub fn load(sftp: &Sftp, config: &Sshconfig, version: &str) -> Result<Option<DeployMetadata>> {
+    let path = Path::new(&config.history_path).join(metadata_file_name(version));
+    let Ok(mut file) = sftp.open(&path) else {
+        return Ok(None);
+    };
+
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(serde_json::from_str(&content).ok())
+}
+
+// 部署完成后写入元数据；重新部署历史版本时沿用原有的构建信息，只更新部署时间
+pub fn save(
+    sess: &Session,
+    config: &Sshconfig,
+    version: &str,
+    metadata: Option<DeployMetadata>,
+) -> Result<()> {
+    let sftp = sess.sftp()?;
+    let mut metadata = match metadata {
+        Some(metadata) => metadata,
+        None => load(&sftp, config, version)?.unwrap_or_else(|| DeployMetadata {
+            version: version.to_string(),
+            ..Default::default()
+        }),
+    };
+    metadata.deploy_time = now();
+    metadata.environment = config.env.clone();
+
+    let path = Path::new(&config.history_path).join(metadata_file_name(version));
+    if config.options.dry_run {
+        dry_run::print("写入部署信息", path.display());
+        return Ok(());
+    }
+
+    let mut file = sftp.create(&path)?;
+    file.write_all(serde_json::to_string_pretty(&metadata)?.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+// 步骤进度显示：执行中显示 spinner 或进度条，结束后打印 ✓/✗ 和耗时。
+// 非终端环境下 indicatif 不绘制动画，只保留结果行，方便在 cron / CI 日志中查看
+use anyhow::Result;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+const SPINNER_CHARS: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏", "✓"];
+
+pub struct Step {
+    name: String,
+    bar: ProgressBar,
+    start: Instant,
+    done: bool,
+}
+
+impl Step {
+    // 显示 spinner 的步骤，适合执行期间没有输出的操作
+    pub fn spinner(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(
+            ProgressStyle::with_template("{spinner:.cyan} {msg} {elapsed:.dim}")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner())
+                .tick_strings(SPINNER_CHARS),
+        );
+        bar.set_message(name.clone());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Step::new(name, bar)
+    }
+
+    // 显示传输进度的步骤，total 为总字节数
+    pub fn bytes(name: impl Into<String>, total: u64) -> Self {
+        let name = name.into();
+        let bar = ProgressBar::new(total);
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{msg} {bar:40.cyan/blue} {bytes}/{total_bytes} {bytes_per_sec} 剩余 {eta}",
+            )
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> "),
+        );
+        bar.set_message(name.clone());
+        Step::new(name, bar)
+    }
+
+    // 不显示动画的步骤，适合执行期间会持续输出或需要交互的操作
+    pub fn plain(name: impl Into<String>) -> Self {
+        let name = name.into();
+        println!("▶ {}", name);
+        Step::new(name, ProgressBar::hidden())
+    }
+
+    fn new(name: String, bar: ProgressBar) -> Self {
+        Step {
+            name,
+            bar,
+            start: Instant::now(),
+            done: false,
+        }
+    }
+
+    pub fn set_position(&self, pos: u64) {
+        self.bar.set_position(pos);
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+    }
+
+    // 暂时隐藏动画输出一行，避免和 spinner 混在一起
+    pub fn println(&self, msg: impl Display) {
+        self.bar.suspend(|| println!("{}", msg));
+    }
+
+    pub fn finish(mut self) {
+        self.end(true);
+    }
+
+    fn end(&mut self, success: bool) {
+        self.done = true;
+        self.bar.finish_and_clear();
+        let mark = if success { "✓" } else { "✗" };
+        println!(
+            "{} {} ({})",
+            mark,
+            self.name,
+            format_elapsed(self.start.elapsed())
+        );
+    }
+}
+
+// 未调用 finish 就被丢弃说明步骤中途出错
+impl Drop for Step {
+    fn drop(&mut self) {
+        if !self.done {
+            self.end(false);
+        }
+    }
+}
+
+// 在 spinner 中执行一个步骤，成功打印 ✓，失败打印 ✗
+pub fn run<T>(name: impl Into<String>, f: impl FnOnce(&Step) -> Result<T>) -> Result<T> {
+    finish_with(Step::spinner(name), f)
+}
+
+// 与 run 相同，但不显示动画
+pub fn run_plain<T>(name: impl Into<String>, f: impl FnOnce(&Step) -> Result<T>) -> Result<T> {
+    finish_with(Step::plain(name), f)
+}
+
+fn finish_with<T>(step: Step, f: impl FnOnce(&Step) -> Result<T>) -> Result<T> {
+    let result = f(&step);
+    if result.is_ok() {
+        step.finish();
+    }
+    result
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
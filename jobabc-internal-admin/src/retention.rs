@@ -0,0 +1,205 @@
+// 历史版本保留策略：清理 history_path 中过旧的压缩包及对应的发布目录
+use crate::archive;
+use crate::config::Sshconfig;
+use crate::dry_run;
+use crate::history::{self, HistoryEntry};
+use crate::metadata;
+use crate::ssh;
+use crate::zip;
+use anyhow::Result;
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use serde::Deserialize;
+use ssh2::Session;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 满足任一条件的版本都会保留；两项都未配置时不清理
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Retention {
+    // 保留最新的 N 个版本
+    pub keep_last: Option<usize>,
+    // 保留最近 N 天内上传的版本
+    pub keep_days: Option<u64>,
+    // 部署成功后自动清理，默认关闭，只能通过 prune 命令手动清理
+    #[serde(default)]
+    pub auto: bool,
+}
+
+impl Retention {
+    pub fn is_configured(&self) -> bool {
+        self.keep_last.is_some() || self.keep_days.is_some()
+    }
+}
+
+// 按保留策略选出可以删除的版本，history 需按从新到旧排序。
+// 当前版本和上一个版本始终保留
+pub fn select_prunable<'a>(
+    policy: &Retention,
+    history: &'a [HistoryEntry],
+    keep: &[Option<String>],
+    now: u64,
+) -> Vec<&'a HistoryEntry> {
+    if !policy.is_configured() {
+        return Vec::new();
+    }
+
+    history
+        .iter()
+        .enumerate()
+        .filter(|(i, entry)| {
+            let recent = policy.keep_last.is_some_and(|n| *i < n);
+            let young = policy
+                .keep_days
+                .is_some_and(|days| now.saturating_sub(entry.mtime) < days * 24 * 60 * 60);
+            let live = keep
+                .iter()
+                .any(|k| k.as_deref() == Some(entry.name.as_str()));
+            !(recent || young || live)
+        })
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+// 列出并删除不在保留策略内的版本，assume_yes 为 false 时需要确认
+pub fn prune(sess: &Session, config: &Sshconfig, assume_yes: bool) -> Result<()> {
+    if !config.retention.is_configured() {
+        println!("{} 环境没有配置 retention，跳过清理", config.env);
+        return Ok(());
+    }
+
+    let history = history::get_history(config, sess)?;
+    let keep = [
+        history::current_version(config, sess)?,
+        history::previous_version(config, sess)?,
+    ];
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let prunable = select_prunable(&config.retention, &history, &keep, now);
+
+    if prunable.is_empty() {
+        println!("没有需要清理的历史版本");
+        return Ok(());
+    }
+
+    println!("以下 {} 个历史版本将被删除:", prunable.len());
+    for entry in &prunable {
+        println!("  {}", entry.name);
+    }
+
+    if config.options.dry_run {
+        for entry in &prunable {
+            dry_run::print("删除历史版本", entry.name.as_str());
+        }
+        return Ok(());
+    }
+
+    if !assume_yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("确认删除？")
+            .default(false)
+            .interact()?
+    {
+        println!("已取消清理");
+        return Ok(());
+    }
+
+    let sftp = sess.sftp()?;
+    let history_path = Path::new(&config.history_path);
+    for entry in prunable {
+        sftp.unlink(&history_path.join(&entry.name))?;
+        // 校验文件和元数据可能不存在（早期版本）
+        sftp.unlink(&history_path.join(archive::checksum_file_name(&entry.name)))
+            .ok();
+        sftp.unlink(&history_path.join(metadata::metadata_file_name(&entry.name)))
+            .ok();
+
+        let release = Path::new(&config.output_path)
+            .join("releases")
+            .join(zip::release_name(&entry.name));
+        ssh::run_step(
+            sess,
+            config,
+            "删除发布目录",
+            &format!("rm -rf {}", ssh::quote(&release.display().to_string())),
+        )?;
+        println!("已删除 {}", entry.name);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+    const NOW: u64 = 100 * DAY;
+
+    // 从新到旧，第 i 个版本在 i 天前上传
+    fn history(names: &[&str]) -> Vec<HistoryEntry> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| HistoryEntry {
+                name: name.to_string(),
+                size: 0,
+                mtime: NOW - i as u64 * DAY,
+            })
+            .collect()
+    }
+
+    fn names(entries: Vec<&HistoryEntry>) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    fn policy(keep_last: Option<usize>, keep_days: Option<u64>) -> Retention {
+        Retention {
+            keep_last,
+            keep_days,
+            auto: false,
+        }
+    }
+
+    #[test]
+    fn unconfigured_policy_prunes_nothing() {
+        let history = history(&["v1.0.3", "v1.0.2", "v1.0.1"]);
+        assert!(select_prunable(&policy(None, None), &history, &[], NOW).is_empty());
+    }
+
+    #[test]
+    fn keep_last_keeps_newest() {
+        let history = history(&["v1.0.3", "v1.0.2", "v1.0.1", "v1.0.0"]);
+        let prunable = select_prunable(&policy(Some(2), None), &history, &[], NOW);
+        assert_eq!(names(prunable), ["v1.0.1", "v1.0.0"]);
+    }
+
+    #[test]
+    fn keep_last_zero_keeps_only_live() {
+        let history = history(&["v1.0.2", "v1.0.1", "v1.0.0"]);
+        let keep = [Some("v1.0.2".to_string()), None];
+        let prunable = select_prunable(&policy(Some(0), None), &history, &keep, NOW);
+        assert_eq!(names(prunable), ["v1.0.1", "v1.0.0"]);
+    }
+
+    #[test]
+    fn keep_days_boundary_is_exclusive() {
+        // 0、1、2 天前上传，keep_days = 2 时正好 2 天前的版本可以删除
+        let history = history(&["v1.0.2", "v1.0.1", "v1.0.0"]);
+        let prunable = select_prunable(&policy(None, Some(2)), &history, &[], NOW);
+        assert_eq!(names(prunable), ["v1.0.0"]);
+    }
+
+    #[test]
+    fn either_rule_keeps_a_version() {
+        let history = history(&["v1.0.3", "v1.0.2", "v1.0.1", "v1.0.0"]);
+        let prunable = select_prunable(&policy(Some(1), Some(3)), &history, &[], NOW);
+        assert_eq!(names(prunable), ["v1.0.0"]);
+    }
+
+    #[test]
+    fn current_and_previous_are_always_kept() {
+        let history = history(&["v1.0.3", "v1.0.2", "v1.0.1", "v1.0.0"]);
+        let keep = [Some("v1.0.0".to_string()), Some("v1.0.1".to_string())];
+        let prunable = select_prunable(&policy(Some(1), None), &history, &keep, NOW);
+        assert_eq!(names(prunable), ["v1.0.2"]);
+    }
+}
@@ -1,59 +1,766 @@
-use crate::config::Sshconfig;
+use crate::config::{JumpHost, Sshconfig};
+use crate::dry_run;
+use crate::known_hosts;
+use crate::progress::{self, Step};
 use anyhow::{Context, Result};
-use ssh2::Session;
-use std::io::{self, Read, Write};
-use std::net::TcpStream;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use dialoguer::{Password, theme::ColorfulTheme};
+use serde::Deserialize;
+use ssh2::{Channel, ErrorCode, OpenFlags, OpenType, Session};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-pub fn connect(config: &Sshconfig, private_key: &str) -> Result<Session> {
-    println!("正在连接到 {}:{}", config.host, config.port);
+// 连接参数，可在 config.json 的 connection 中按环境覆盖
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConnectionSettings {
+    // TCP 连接超时（秒）
+    pub connect_timeout: u64,
+    // 等待服务器响应的超时（秒），0 表示不限制
+    pub read_timeout: u64,
+    // 连接失败后的重试次数，每次等待时间翻倍
+    pub retries: u32,
+    // 发送 keepalive 的间隔（秒），0 表示关闭
+    pub keepalive_interval: u32,
+}
 
-    // 启动动态加载动画
-    let loading_chars = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-    let mut i = 0;
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        ConnectionSettings {
+            connect_timeout: 10,
+            read_timeout: 300,
+            retries: 3,
+            keepalive_interval: 30,
+        }
+    }
+}
 
-    // 在新线程中显示动画
-    let handle = thread::spawn(move || {
-        loop {
-            print!("\r{} 连接中...", loading_chars[i]);
-            io::stdout().flush().unwrap();
-            thread::sleep(Duration::from_millis(100));
-            i = (i + 1) % loading_chars.len();
+pub fn connect(config: &Sshconfig) -> Result<Session> {
+    let name = format!("连接 {}:{}", config.host, config.port());
+    // 配置了跳板机时经跳板机转发
+    let sess = match config.jump_host()? {
+        Some(jump) => {
+            let tcp = tunnel(&jump, config)?;
+            progress::run(name, |_| handshake(tcp, &config.connection))
         }
-    });
+        None => progress::run(name, |step| {
+            let tcp = tcp_connect(&config.host, config.port(), &config.connection, step)?;
+            handshake(tcp, &config.connection)
+        }),
+    }
+    .with_context(|| format!("无法连接到服务器 {}:{}", config.host, config.port()))?;
+    known_hosts::verify(
+        &sess,
+        &config.host,
+        config.port(),
+        config.options.strict_host_key_checking,
+    )?;
+
+    progress::run_plain(
+        format!("SSH认证 {}@{}", config.username, config.host),
+        |_| authenticate(&sess, &config.username, config.identity_paths()?),
+    )?;
+
+    Ok(sess)
+}
 
-    // 尝试连接
-    let tcp = TcpStream::connect(format!("{}:{}", config.host, config.port))
-        .with_context(|| format!("无法连接到服务器 {}:{}", config.host, config.port))?;
+// 在已建立的 TCP 连接上完成 SSH 握手
+fn handshake(tcp: TcpStream, settings: &ConnectionSettings) -> Result<Session> {
     let mut sess = Session::new().with_context(|| "创建SSH会话失败")?;
     sess.set_tcp_stream(tcp);
+    apply_settings(&sess, settings);
     sess.handshake().with_context(|| "SSH握手失败")?;
+    Ok(sess)
+}
 
-    // 停止动画
-    handle.thread().unpark();
-    print!("\r✓ 连接成功！\n");
+// 带超时的 TCP 连接，失败后按 1、2、4… 秒的间隔重试
+fn tcp_connect(
+    host: &str,
+    port: u16,
+    settings: &ConnectionSettings,
+    step: &Step,
+) -> Result<TcpStream> {
+    let timeout = Duration::from_secs(settings.connect_timeout.max(1));
+    let mut delay = Duration::from_secs(1);
+    let mut attempt = 0;
 
-    println!("正在使用密钥认证...");
-    // 使用私钥认证
-    sess.userauth_pubkey_memory(&config.username, None, private_key, None)
-        .with_context(|| format!("SSH密钥认证失败，用户名: {}", config.username))?;
+    loop {
+        let result = (host, port)
+            .to_socket_addrs()
+            .map_err(anyhow::Error::from)
+            .and_then(|addrs| {
+                let mut last_err = None;
+                for addr in addrs {
+                    match TcpStream::connect_timeout(&addr, timeout) {
+                        Ok(tcp) => return Ok(tcp),
+                        Err(e) => last_err = Some(e),
+                    }
+                }
+                Err(last_err.map_or_else(|| anyhow::anyhow!("无法解析主机名 {}", host), Into::into))
+            });
 
-    if !sess.authenticated() {
-        return Err(anyhow::anyhow!("SSH认证失败，请检查密钥是否正确"));
+        match result {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) if attempt < settings.retries => {
+                attempt += 1;
+                step.println(format!(
+                    "连接 {}:{} 失败: {}，{} 秒后第 {} 次重试",
+                    host,
+                    port,
+                    e,
+                    delay.as_secs(),
+                    attempt
+                ));
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(e) => return Err(e),
+        }
     }
+}
 
-    println!("SSH连接成功！");
-    Ok(sess)
+// 设置阻塞调用的超时和 keepalive 间隔
+fn apply_settings(sess: &Session, settings: &ConnectionSettings) {
+    sess.set_timeout(millis(settings.read_timeout));
+    sess.set_keepalive(false, settings.keepalive_interval);
+}
+
+// libssh2 的超时以毫秒为单位
+fn millis(secs: u64) -> u32 {
+    secs.saturating_mul(1000).min(u32::MAX as u64) as u32
+}
+
+// 检查会话是否仍然可用，已断开时重新连接，例如构建耗时过长期间 VPN 断开
+pub fn ensure_connected(sess: &Session, config: &Sshconfig) -> Result<Session> {
+    if config.options.dry_run || is_alive(sess, &config.connection) {
+        return Ok(sess.clone());
+    }
+    println!("SSH 连接已断开，正在重新连接...");
+    connect(config)
+}
+
+fn is_alive(sess: &Session, settings: &ConnectionSettings) -> bool {
+    // 用连接超时代替读超时，避免在已断开的连接上等待太久
+    let timeout = sess.timeout();
+    sess.set_timeout(millis(settings.connect_timeout.max(1)));
+    let alive = execute(sess, "true").is_ok_and(|output| output.success());
+    sess.set_timeout(timeout);
+    alive
+}
+
+// libssh2 在非阻塞模式下暂时无法发送时的错误码
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+
+// 在后台定期发送 keepalive，防止本地构建和部署期间连接因空闲被断开；drop 时停止
+pub struct KeepAlive {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+pub fn keep_alive(sess: &Session, settings: &ConnectionSettings) -> KeepAlive {
+    let stop = Arc::new(AtomicBool::new(false));
+    let interval = settings.keepalive_interval;
+    let handle = (interval > 0).then(|| {
+        let sess = sess.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let mut elapsed = 0;
+            while !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_secs(1));
+                elapsed += 1;
+                if elapsed >= interval {
+                    elapsed = 0;
+                    // 其他线程正在以非阻塞方式读取时会返回 EAGAIN，下次再发；
+                    // 其他错误说明连接已断开，上传前会重新连接
+                    match sess.keepalive_send() {
+                        Err(e) if e.code() != ErrorCode::Session(LIBSSH2_ERROR_EAGAIN) => break,
+                        _ => {}
+                    }
+                }
+            }
+        })
+    });
+
+    KeepAlive { stop, handle }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+// 先连接并认证跳板机，再通过 direct-tcpip 通道连接目标服务器。
+// ssh2 的 Session 需要一个真实的 socket，所以在本地回环地址上转发通道中的数据
+fn tunnel(jump: &JumpHost, config: &Sshconfig) -> Result<TcpStream> {
+    let bastion = progress::run(
+        format!("连接跳板机 {}:{}", jump.host, jump.port),
+        |step| {
+            let tcp = tcp_connect(&jump.host, jump.port, &config.connection, step)?;
+            handshake(tcp, &config.connection)
+        },
+    )
+    .with_context(|| format!("无法连接到跳板机 {}:{}", jump.host, jump.port))?;
+    known_hosts::verify(
+        &bastion,
+        &jump.host,
+        jump.port,
+        config.options.strict_host_key_checking,
+    )?;
+    progress::run_plain(format!("SSH认证 {}@{}", jump.username, jump.host), |_| {
+        authenticate(&bastion, &jump.username, jump.identity_paths()?)
+    })?;
+
+    let channel = bastion
+        .channel_direct_tcpip(&config.host, config.port(), None)
+        .with_context(|| {
+            format!(
+                "跳板机无法连接到目标服务器 {}:{}",
+                config.host,
+                config.port()
+            )
+        })?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let local = TcpStream::connect(listener.local_addr()?)?;
+    // 只接受刚才发起的连接，其他本地进程抢先连上监听端口时直接断开
+    let expected = local.local_addr()?;
+    let forwarded = loop {
+        let (stream, peer) = listener.accept()?;
+        if peer == expected {
+            break stream;
+        }
+        eprintln!("忽略来自 {} 的本地连接", peer);
+    };
+    drop(listener);
+    thread::spawn(move || {
+        if let Err(e) = forward(&bastion, channel, forwarded) {
+            eprintln!("跳板机转发中断: {}", e);
+        }
+    });
+
+    Ok(local)
+}
+
+// 在本地 socket 和跳板机通道之间双向转发，任意一端关闭后结束
+fn forward(bastion: &Session, mut channel: Channel, mut socket: TcpStream) -> Result<()> {
+    bastion.set_blocking(false);
+    socket.set_nonblocking(true)?;
+    let mut buf = vec![0u8; UPLOAD_CHUNK_SIZE];
+
+    loop {
+        let mut active = false;
+
+        match socket.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                write_all_nonblocking(&mut channel, &buf[..n])?;
+                active = true;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        match channel.read(&mut buf) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => {}
+            Ok(n) => {
+                write_all_nonblocking(&mut socket, &buf[..n])?;
+                active = true;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if !active {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    Ok(())
+}
+
+// 非阻塞模式下写入全部数据，对端暂时无法接收时稍后重试
+fn write_all_nonblocking(writer: &mut impl Write, mut data: &[u8]) -> Result<()> {
+    while !data.is_empty() {
+        match writer.write(data) {
+            Ok(0) => anyhow::bail!("连接已关闭"),
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(1))
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
 }
 
-pub fn execute_command(sess: &Session, command: &str) -> Result<String> {
+// libssh2 无法读取私钥时的错误码，加密的私钥未提供密码时也会返回该错误
+const LIBSSH2_ERROR_FILE: i32 = -16;
+
+// 输入私钥密码的次数
+const PASSPHRASE_ATTEMPTS: usize = 3;
+
+// 先按顺序尝试配置的私钥，ssh-agent 中有对应公钥时通过 agent 认证，否则读取私钥文件，
+// 加密的私钥会提示输入密码；最后再尝试 ssh-agent 中的其他密钥。配置的私钥排在前面，
+// 避免 agent 中的密钥过多，超过服务器的 MaxAuthTries 后被断开
+fn authenticate(sess: &Session, username: &str, identity_paths: Vec<PathBuf>) -> Result<()> {
+    let keys: Vec<PathBuf> = identity_paths
+        .into_iter()
+        .filter(|path| path.is_file())
+        .collect();
+    let agent = connect_agent(sess);
+    let mut agent_keys = match &agent {
+        Some(agent) => agent.identities().unwrap_or_default(),
+        None => Vec::new(),
+    };
+    if keys.is_empty() && agent_keys.is_empty() {
+        anyhow::bail!(
+            "ssh-agent 中没有密钥且没有找到可用的私钥，请在 config.json 的 identity_files 或 .env 的 <ENV>_IDENTITY_FILES 中配置"
+        );
+    }
+
+    let authenticated = try_identities(sess, username, &keys, agent.as_ref(), &mut agent_keys);
+    if let Some(mut agent) = agent {
+        agent.disconnect().ok();
+    }
+    if authenticated {
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "SSH认证失败，用户名: {}，已尝试的密钥: {}",
+        username,
+        keys.iter()
+            .map(|key| key.display().to_string())
+            .chain(
+                agent_keys
+                    .iter()
+                    .map(|identity| identity.comment().to_string())
+            )
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+// 依次尝试配置的私钥和 ssh-agent 中剩余的密钥，认证成功时返回 true；
+// 通过 agent 尝试过的密钥会从 agent_keys 中移除
+fn try_identities(
+    sess: &Session,
+    username: &str,
+    keys: &[PathBuf],
+    agent: Option<&ssh2::Agent>,
+    agent_keys: &mut Vec<ssh2::PublicKey>,
+) -> bool {
+    for key in keys {
+        let blob = public_key_blob(key);
+        let in_agent = agent_keys
+            .iter()
+            .position(|identity| Some(identity.blob()) == blob.as_deref());
+        if let (Some(agent), Some(i)) = (agent, in_agent) {
+            let identity = agent_keys.remove(i);
+            println!("正在使用 ssh-agent 中的密钥认证: {}", key.display());
+            if agent.userauth(username, &identity).is_ok() && sess.authenticated() {
+                return true;
+            }
+            continue;
+        }
+
+        println!("正在使用密钥认证: {}", key.display());
+        match userauth_key(sess, username, key) {
+            Ok(()) if sess.authenticated() => return true,
+            Ok(()) => {}
+            Err(e) => println!("✗ {}", e),
+        }
+    }
+
+    if let Some(agent) = agent {
+        for identity in agent_keys.iter() {
+            println!("正在使用 ssh-agent 中的密钥认证: {}", identity.comment());
+            if agent.userauth(username, identity).is_ok() && sess.authenticated() {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+// 连接 ssh-agent 并读取其中的密钥；未运行 ssh-agent 或读取出错时按没有 agent 处理
+fn connect_agent(sess: &Session) -> Option<ssh2::Agent> {
+    env::var_os("SSH_AUTH_SOCK")?;
+    let mut agent = sess.agent().ok()?;
+    agent.connect().ok()?;
+    if agent.list_identities().is_err() {
+        agent.disconnect().ok();
+        return None;
+    }
+    Some(agent)
+}
+
+// 读取私钥旁边 .pub 文件中的公钥，用于在 ssh-agent 中查找同一个密钥
+fn public_key_blob(key: &Path) -> Option<Vec<u8>> {
+    let content = fs::read_to_string(format!("{}.pub", key.display())).ok()?;
+    STANDARD.decode(content.split_whitespace().nth(1)?).ok()
+}
+
+fn userauth_key(sess: &Session, username: &str, key: &Path) -> Result<()> {
+    let err = match sess.userauth_pubkey_file(username, None, key, None) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    if err.code() != ErrorCode::Session(LIBSSH2_ERROR_FILE) {
+        return Err(anyhow::anyhow!("密钥 {} 认证失败: {}", key.display(), err));
+    }
+
+    // 私钥已加密，提示输入密码
+    for _ in 0..PASSPHRASE_ATTEMPTS {
+        let passphrase = Password::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("请输入私钥 {} 的密码", key.display()))
+            .allow_empty_password(true)
+            .interact()?;
+        match sess.userauth_pubkey_file(username, None, key, Some(&passphrase)) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.code() == ErrorCode::Session(LIBSSH2_ERROR_FILE) => {
+                println!("密码错误或无法读取私钥");
+            }
+            Err(e) => return Err(anyhow::anyhow!("密钥 {} 认证失败: {}", key.display(), e)),
+        }
+    }
+
+    anyhow::bail!("私钥 {} 密码错误次数过多", key.display())
+}
+
+// 远程命令的执行结果
+#[derive(Debug)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_status: i32,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_status == 0
+    }
+}
+
+// 执行远程命令，返回输出和退出码
+pub fn execute(sess: &Session, command: &str) -> Result<CommandOutput> {
     let mut channel = sess.channel_session()?;
     channel.exec(command)?;
 
-    let mut output = String::new();
-    channel.read_to_string(&mut output)?;
+    // 同时读取 stdout 和 stderr，避免 stderr 写满缓冲区后远程命令阻塞
+    sess.set_blocking(false);
+    let result = read_channel(sess, &mut channel, |_| {}, |_| {});
+    sess.set_blocking(true);
+    let (stdout, stderr) = result?;
+
     channel.wait_close()?;
 
-    Ok(output)
+    Ok(CommandOutput {
+        stdout,
+        stderr,
+        exit_status: channel.exit_status()?,
+    })
+}
+
+// 执行远程命令，实时逐行输出 stdout/stderr，每行带上步骤前缀
+pub fn execute_streaming(sess: &Session, step: &str, command: &str) -> Result<CommandOutput> {
+    let mut channel = sess.channel_session()?;
+    channel.exec(command)?;
+
+    // 非阻塞读取，才能同时转发 stdout 和 stderr
+    sess.set_blocking(false);
+    let result = stream_channel(sess, &mut channel, step);
+    sess.set_blocking(true);
+    let (stdout, stderr) = result?;
+
+    channel.wait_close()?;
+
+    Ok(CommandOutput {
+        stdout,
+        stderr,
+        exit_status: channel.exit_status()?,
+    })
+}
+
+fn stream_channel(sess: &Session, channel: &mut Channel, step: &str) -> Result<(String, String)> {
+    read_channel(
+        sess,
+        channel,
+        |line| println!("[{}] {}", step, line),
+        |line| eprintln!("[{}] {}", step, line),
+    )
+}
+
+// 在非阻塞模式下轮流读取 stdout 和 stderr，每读到一行调用对应的回调。
+// 非阻塞读取不受会话超时限制，这里按 read_timeout 自行判断服务器是否无响应
+fn read_channel(
+    sess: &Session,
+    channel: &mut Channel,
+    on_stdout: impl Fn(&str),
+    on_stderr: impl Fn(&str),
+) -> Result<(String, String)> {
+    let mut stdout = LineBuffer::default();
+    let mut stderr = LineBuffer::default();
+    let mut buf = [0u8; 4096];
+    let timeout = Duration::from_millis(sess.timeout() as u64);
+    let mut last_received = Instant::now();
+
+    loop {
+        let mut received = false;
+
+        match channel.read(&mut buf) {
+            Ok(0) => {}
+            Ok(n) => {
+                received = true;
+                stdout.push(&buf[..n], &on_stdout);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        match channel.stderr().read(&mut buf) {
+            Ok(0) => {}
+            Ok(n) => {
+                received = true;
+                stderr.push(&buf[..n], &on_stderr);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if received {
+            last_received = Instant::now();
+        } else {
+            if channel.eof() {
+                break;
+            }
+            if !timeout.is_zero() && last_received.elapsed() > timeout {
+                anyhow::bail!("等待服务器响应超时（{} 秒）", timeout.as_secs());
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    stdout.finish(&on_stdout);
+    stderr.finish(&on_stderr);
+    Ok((stdout.output, stderr.output))
+}
+
+// 按行切分输出，未结束的行留到下次再输出
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+    output: String,
+}
+
+impl LineBuffer {
+    fn push(&mut self, data: &[u8], mut emit: impl FnMut(&str)) {
+        self.pending.extend_from_slice(data);
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            emit(line.trim_end());
+            self.output.push_str(&line);
+        }
+    }
+
+    fn finish(&mut self, mut emit: impl FnMut(&str)) {
+        if !self.pending.is_empty() {
+            let line = String::from_utf8_lossy(&self.pending).to_string();
+            emit(line.trim_end());
+            self.output.push_str(&line);
+            self.pending.clear();
+        }
+    }
+}
+
+// 执行部署步骤，退出码非 0 时带上 stderr 返回错误
+pub fn run_step(sess: &Session, config: &Sshconfig, step: &str, command: &str) -> Result<String> {
+    if config.options.dry_run {
+        dry_run::print(step, command);
+        return Ok(String::new());
+    }
+    progress::run(step, |_| {
+        let output = execute(sess, command).with_context(|| format!("{}失败", step))?;
+        check_step(step, command, output)
+    })
+}
+
+// 与 run_step 相同，但实时输出命令执行过程，适合耗时较长的步骤
+pub fn run_step_streaming(
+    sess: &Session,
+    config: &Sshconfig,
+    step: &str,
+    command: &str,
+) -> Result<String> {
+    if config.options.dry_run {
+        dry_run::print(step, command);
+        return Ok(String::new());
+    }
+    progress::run_plain(step, |_| {
+        let output =
+            execute_streaming(sess, step, command).with_context(|| format!("{}失败", step))?;
+        check_step(step, command, output)
+    })
+}
+
+fn check_step(step: &str, command: &str, output: CommandOutput) -> Result<String> {
+    if !output.success() {
+        let detail = if output.stderr.trim().is_empty() {
+            output.stdout.trim()
+        } else {
+            output.stderr.trim()
+        };
+        anyhow::bail!(
+            "{}失败（退出码 {}）: {}\n命令: {}",
+            step,
+            output.exit_status,
+            detail,
+            command
+        );
+    }
+    Ok(output.stdout)
+}
+
+// 用单引号包裹参数，避免路径中的空格和特殊字符被 shell 解析
+pub fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+// 在服务器上校验上传完成的 .part 文件
+fn verify_part(sess: &Session, part: &Path, total: u64, sha256: &str) -> Result<()> {
+    let uploaded = sess.sftp()?.stat(part)?.size.unwrap_or(0);
+    if uploaded != total {
+        anyhow::bail!(
+            "上传大小不一致: 本地 {} 字节，远程 {} 字节",
+            total,
+            uploaded
+        );
+    }
+
+    let output = execute(sess, &format!("sha256sum {}", part.display()))?;
+    let remote = output.stdout.split_whitespace().next().unwrap_or_default();
+    if !output.success() || remote != sha256 {
+        anyhow::bail!(
+            "上传后校验失败: 本地 SHA-256 {}，远程 {}",
+            sha256,
+            if remote.is_empty() {
+                output.stderr.trim()
+            } else {
+                remote
+            }
+        );
+    }
+    Ok(())
+}
+
+// 分块上传的大小
+const UPLOAD_CHUNK_SIZE: usize = 256 * 1024;
+
+// 分块上传文件并显示进度。先写入 <remote>.part，校验大小和 SHA-256 通过后再重命名，
+// 校验失败时删除 .part。<remote>.part.sha256 记录 .part 对应的本地压缩包，一致时才从
+// 已上传的位置继续（上次上传中断的情况）。传输中断时重新连接并续传，最多 retries 次
+pub fn upload_file(
+    sess: &mut Session,
+    config: &Sshconfig,
+    local: &Path,
+    remote: &Path,
+    sha256: &str,
+) -> Result<()> {
+    let total = File::open(local)?.metadata()?.len();
+    let part = PathBuf::from(format!("{}.part", remote.display()));
+    let sidecar = PathBuf::from(format!("{}.part.sha256", remote.display()));
+
+    let name = remote
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let step = Step::bytes(format!("上传 {}", name), total);
+
+    let mut attempt = 0;
+    while let Err(e) = upload_part(sess, local, &part, &sidecar, total, sha256, &step) {
+        if attempt >= config.connection.retries {
+            return Err(e);
+        }
+        attempt += 1;
+        step.println(format!(
+            "上传中断: {}，重新连接后继续（第 {}/{} 次）",
+            e, attempt, config.connection.retries
+        ));
+        *sess = ensure_connected(sess, config)?;
+    }
+
+    // 校验通过后再替换正式文件，损坏的压缩包不会出现在历史版本中
+    let sftp = sess.sftp()?;
+    if let Err(e) = verify_part(sess, &part, total, sha256) {
+        sftp.unlink(&part).ok();
+        sftp.unlink(&sidecar).ok();
+        return Err(e);
+    }
+    sftp.unlink(remote).ok();
+    sftp.rename(&part, remote, None)?;
+    sftp.unlink(&sidecar).ok();
+    step.finish();
+
+    Ok(())
+}
+
+// 从 .part 的当前大小继续上传；.part 不属于这个压缩包或比本地文件大时重新上传
+fn upload_part(
+    sess: &Session,
+    local: &Path,
+    part: &Path,
+    sidecar: &Path,
+    total: u64,
+    sha256: &str,
+    step: &Step,
+) -> Result<()> {
+    let sftp = sess.sftp()?;
+    let mut owner = String::new();
+    if let Ok(mut file) = sftp.open(sidecar) {
+        file.read_to_string(&mut owner).ok();
+    }
+    let offset = match sftp.stat(part) {
+        Ok(stat) if owner.trim() == sha256 => stat.size.filter(|&size| size <= total).unwrap_or(0),
+        _ => 0,
+    };
+
+    let mut remote_file = if offset > 0 {
+        step.println(format!("检测到未完成的上传，从 {} 字节处继续", offset));
+        let mut file = sftp.open_mode(part, OpenFlags::WRITE, 0o644, OpenType::File)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file
+    } else {
+        let file = sftp.create(part)?;
+        sftp.create(sidecar)?.write_all(sha256.as_bytes())?;
+        file
+    };
+    let mut local_file = File::open(local)?;
+    local_file.seek(SeekFrom::Start(offset))?;
+    step.set_position(offset);
+
+    let mut buf = vec![0u8; UPLOAD_CHUNK_SIZE];
+    loop {
+        let n = local_file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        remote_file.write_all(&buf[..n])?;
+        step.inc(n as u64);
+    }
+    remote_file.fsync().ok();
+    Ok(())
 }
@@ -0,0 +1,206 @@
+// 读取 ~/.ssh/config，按主机别名解析 HostName、User、Port、IdentityFile 和 ProxyJump。
+// 与 ssh 客户端一致：同一个选项以第一次出现的值为准，IdentityFile 可以有多个
+use anyhow::Result;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Clone)]
+pub struct HostConfig {
+    pub host_name: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_files: Vec<String>,
+    pub proxy_jump: Option<String>,
+}
+
+// 读取当前用户的 ~/.ssh/config，文件不存在时返回空配置
+pub fn resolve(alias: &str) -> Result<HostConfig> {
+    let path = PathBuf::from(env::var("HOME")?).join(".ssh").join("config");
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(parse(&content, alias)),
+        Err(_) => Ok(HostConfig::default()),
+    }
+}
+
+pub fn parse(content: &str, alias: &str) -> HostConfig {
+    let mut config = HostConfig::default();
+    // 第一个 Host 之前的选项对所有主机生效
+    let mut matched = true;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // 关键字和值之间可以用空格或等号分隔
+        let (key, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((key, value)) => (
+                key,
+                value.trim_start_matches(|c: char| c.is_whitespace() || c == '='),
+            ),
+            None => (line, ""),
+        };
+        let value = value.trim().trim_matches('"');
+
+        match key.to_lowercase().as_str() {
+            "host" => matched = host_matches(value, alias),
+            // 不支持 Match 条件，跳过其中的选项
+            "match" => matched = false,
+            _ if !matched => {}
+            "hostname" => set_once(&mut config.host_name, value),
+            "user" => set_once(&mut config.user, value),
+            "port" if config.port.is_none() => config.port = value.parse().ok(),
+            "identityfile" => config.identity_files.push(value.to_string()),
+            "proxyjump" if !value.eq_ignore_ascii_case("none") => {
+                set_once(&mut config.proxy_jump, value)
+            }
+            _ => {}
+        }
+    }
+
+    // HostName 中的 %h 表示别名本身
+    if let Some(host_name) = &mut config.host_name {
+        *host_name = host_name.replace("%h", alias);
+    }
+
+    config
+}
+
+fn set_once(field: &mut Option<String>, value: &str) {
+    if field.is_none() && !value.is_empty() {
+        *field = Some(value.to_string());
+    }
+}
+
+// Host 后可以有多个模式，! 开头的模式匹配时整行不匹配
+fn host_matches(patterns: &str, alias: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split_whitespace() {
+        match pattern.strip_prefix('!') {
+            Some(pattern) if wildcard_match(pattern, alias) => return false,
+            Some(_) => {}
+            None => matched |= wildcard_match(pattern, alias),
+        }
+    }
+    matched
+}
+
+// 支持 * 和 ? 通配符
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((sp, st)) = star {
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+# 全局选项
+IdentityFile ~/.ssh/global
+
+Host prod prod-*
+    HostName=%h.example.com
+    User forge
+    Port 2222
+    IdentityFile "~/.ssh/prod key"
+    ProxyJump bastion
+
+Host *.internal !db.internal
+    User deploy
+
+Host *
+    User nobody
+    Port 22
+    IdentityFile ~/.ssh/id_ed25519
+    ProxyJump none
+"#;
+
+    #[test]
+    fn parses_matching_host() {
+        let config = parse(CONFIG, "prod");
+        assert_eq!(config.host_name.as_deref(), Some("prod.example.com"));
+        assert_eq!(config.user.as_deref(), Some("forge"));
+        assert_eq!(config.port, Some(2222));
+        assert_eq!(config.proxy_jump.as_deref(), Some("bastion"));
+        assert_eq!(
+            config.identity_files,
+            ["~/.ssh/global", "~/.ssh/prod key", "~/.ssh/id_ed25519"]
+        );
+    }
+
+    #[test]
+    fn first_value_wins() {
+        let config = parse(CONFIG, "prod-2");
+        assert_eq!(config.host_name.as_deref(), Some("prod-2.example.com"));
+        assert_eq!(config.user.as_deref(), Some("forge"));
+        assert_eq!(config.port, Some(2222));
+    }
+
+    #[test]
+    fn negated_pattern_excludes_host() {
+        assert_eq!(
+            parse(CONFIG, "web.internal").user.as_deref(),
+            Some("deploy")
+        );
+        assert_eq!(parse(CONFIG, "db.internal").user.as_deref(), Some("nobody"));
+    }
+
+    #[test]
+    fn proxy_jump_none_is_ignored() {
+        let config = parse(CONFIG, "staging");
+        assert_eq!(config.host_name, None);
+        assert_eq!(config.proxy_jump, None);
+    }
+
+    #[test]
+    fn match_blocks_are_skipped() {
+        let config = parse(
+            "Match host prod\n    User match\nHost prod\n    User host",
+            "prod",
+        );
+        assert_eq!(config.user.as_deref(), Some("host"));
+    }
+
+    #[test]
+    fn host_patterns() {
+        assert!(host_matches("a b", "b"));
+        assert!(!host_matches("!b *", "b"));
+        assert!(host_matches("!b *", "c"));
+        // 只有否定模式时不匹配任何主机
+        assert!(!host_matches("!b", "c"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("prod-?", "prod-1"));
+        assert!(!wildcard_match("prod-?", "prod-12"));
+        assert!(wildcard_match("*.example.*", "a.b.example.com"));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(!wildcard_match("a*b", "aXbY"));
+        assert!(!wildcard_match("prod", "production"));
+    }
+}
@@ -1,79 +1,177 @@
 // 版本管理
 
+use crate::archive;
 use anyhow::Result;
 use dialoguer::{Select, theme::ColorfulTheme};
 use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
 
-#[derive(Debug)]
-pub struct Version {
-    major: u32,
-    minor: u32,
-    patch: u32,
+// 语义化版本号，支持预发布标签和构建元数据，例如 v1.4.0-rc.1+build.5。
+// 比较和排序遵循 SemVer：预发布版本低于对应的正式版本，构建元数据不参与比较
+#[derive(Debug, Clone)]
+pub struct Version(semver::Version);
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
 }
 
-impl Version {
-    pub fn from_str(s: &str) -> Option<Self> {
-        let s = s.trim_start_matches("v").trim_end_matches(".zip");
-        let parts: Vec<&str> = s.split('.').collect();
-        if parts.len() != 3 {
-            return None;
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_precedence(&other.0)
+    }
+}
+
+// 版本自增方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BumpKind {
+    Major,
+    Minor,
+    Patch,
+    Prerelease,
+}
+
+impl FromStr for BumpKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "major" => Ok(BumpKind::Major),
+            "minor" => Ok(BumpKind::Minor),
+            // bump 是原有写法，等同于 patch
+            "patch" | "bump" => Ok(BumpKind::Patch),
+            "prerelease" => Ok(BumpKind::Prerelease),
+            _ => anyhow::bail!("无效的自增方式: {}", s),
+        }
+    }
+}
+
+impl BumpKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BumpKind::Major => "major",
+            BumpKind::Minor => "minor",
+            BumpKind::Patch => "patch",
+            BumpKind::Prerelease => "prerelease",
         }
+    }
+}
 
-        let major = parts[0].parse().ok()?;
-        let minor = parts[1].parse().ok()?;
-        let patch = parts[2].parse().ok()?;
+// 没有预发布标签时新建的标签名
+const PRERELEASE_TAG: &str = "rc";
 
-        Some(Version {
-            major,
-            minor,
-            patch,
-        })
+// 接受 v1.2.3、1.2.3、v1.4.0-rc.1+build.5，可带压缩包后缀
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = archive::strip_extension(s.trim());
+        let trimmed = trimmed.strip_prefix('v').unwrap_or(trimmed);
+        semver::Version::parse(trimmed)
+            .map(Version)
+            .map_err(|_| anyhow::anyhow!("无效的版本号: {}", s.trim()))
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("v{}.{}.{}.zip", self.major, self.minor, self.patch)
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version(semver::Version::new(major, minor, patch))
     }
 
-    pub fn increment(&self) -> Self {
-        let mut new_version = self.clone();
-        new_version.patch += 1;
-        if new_version.patch > 99 {
-            new_version.patch = 0;
-            new_version.minor += 1;
-            if new_version.minor > 99 {
-                new_version.minor = 0;
-                new_version.major += 1;
+    // 按指定方式自增，构建元数据不会保留。
+    // 预发布版本按 SemVer 规则先发布为对应的正式版本，例如 v2.0.0-rc.1 升级主版本得到 v2.0.0
+    pub fn bump(&self, kind: BumpKind) -> Self {
+        let mut v = self.0.clone();
+        let pre = !v.pre.is_empty();
+        v.build = semver::BuildMetadata::EMPTY;
+
+        match kind {
+            BumpKind::Major => {
+                if !(pre && v.minor == 0 && v.patch == 0) {
+                    v.major += 1;
+                }
+                v.minor = 0;
+                v.patch = 0;
+                v.pre = semver::Prerelease::EMPTY;
+            }
+            BumpKind::Minor => {
+                if !(pre && v.patch == 0) {
+                    v.minor += 1;
+                }
+                v.patch = 0;
+                v.pre = semver::Prerelease::EMPTY;
+            }
+            BumpKind::Patch => {
+                if !pre {
+                    v.patch += 1;
+                }
+                v.pre = semver::Prerelease::EMPTY;
+            }
+            BumpKind::Prerelease => {
+                let tag = if pre {
+                    next_prerelease(v.pre.as_str())
+                } else {
+                    v.patch += 1;
+                    format!("{}.1", PRERELEASE_TAG)
+                };
+                // 由合法标签递增得到，不会解析失败
+                v.pre = semver::Prerelease::new(&tag).unwrap_or(semver::Prerelease::EMPTY);
             }
         }
-        new_version
+
+        Version(v)
     }
 }
 
-impl Clone for Version {
-    fn clone(&self) -> Self {
-        Version {
-            major: self.major,
-            minor: self.minor,
-            patch: self.patch,
-        }
+// 预发布标签的最后一段是数字时加一，否则追加 .1，例如 rc.1 -> rc.2、beta -> beta.1
+fn next_prerelease(pre: &str) -> String {
+    let (head, last) = match pre.rsplit_once('.') {
+        Some((head, last)) => (Some(head), last),
+        None => (None, pre),
+    };
+    match (head, last.parse::<u64>()) {
+        (Some(head), Ok(n)) => format!("{}.{}", head, n + 1),
+        (None, Ok(n)) => (n + 1).to_string(),
+        (_, Err(_)) => format!("{}.1", pre),
+    }
+}
+
+// 显示为 v1.2.3 形式，不带压缩包后缀
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
     }
 }
 
 pub fn get_latest_version(history: &[String]) -> Option<Version> {
     history
         .iter()
-        .filter_map(|s| Version::from_str(s))
-        .max_by(|a, b| match a.major.cmp(&b.major) {
-            Ordering::Equal => match a.minor.cmp(&b.minor) {
-                Ordering::Equal => a.patch.cmp(&b.patch),
-                other => other,
-            },
-            other => other,
-        })
+        .filter_map(|s| s.parse::<Version>().ok())
+        .max()
+}
+
+// 获取比指定版本旧的最新版本，未指定时返回最新版本
+pub fn get_latest_version_before(history: &[String], before: Option<&Version>) -> Option<Version> {
+    history
+        .iter()
+        .filter_map(|s| s.parse::<Version>().ok())
+        .filter(|v| before.is_none_or(|b| v < b))
+        .max()
 }
 
 pub fn show_version_menu() -> Result<String> {
-    let options = vec!["版本自增", "指定版本", "历史版本"];
+    let options = vec!["版本自增", "指定版本", "历史版本", "Git 标签"];
 
     let selection = Select::with_theme(&ColorfulTheme::default())
         .with_prompt("请选择版本管理方式")
@@ -84,6 +182,25 @@ pub fn show_version_menu() -> Result<String> {
     Ok(options[selection].to_string())
 }
 
+// 选择自增方式，默认自增修订号
+pub fn select_bump_kind() -> Result<BumpKind> {
+    let options = [
+        ("修订号 (patch)", BumpKind::Patch),
+        ("次版本 (minor)", BumpKind::Minor),
+        ("主版本 (major)", BumpKind::Major),
+        ("预发布 (prerelease)", BumpKind::Prerelease),
+    ];
+    let labels: Vec<&str> = options.iter().map(|(label, _)| *label).collect();
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("请选择自增方式")
+        .items(&labels)
+        .default(0)
+        .interact()?;
+
+    Ok(options[selection].1)
+}
+
 pub fn select_history_version(history: &[String]) -> Result<Option<String>> {
     if history.is_empty() {
         println!("没有历史版本");
@@ -100,5 +217,143 @@ pub fn select_history_version(history: &[String]) -> Result<Option<String>> {
 }
 
 pub fn validate_version(version: &str) -> bool {
-    Version::from_str(version).is_some()
+    version.parse::<Version>().is_ok()
+}
+
+// 版本选择方式，命令行参数和交互菜单共用
+#[derive(Debug, Clone)]
+pub enum VersionChoice {
+    Increment(BumpKind),
+    Specified(String),
+    History(String),
+    // 根据 git 标签和约定式提交计算版本，部署成功后打标签
+    Git,
+}
+
+// 支持 bump/major/minor/patch/prerelease、git、v1.2.3、history:v1.2.0 四种写法
+impl FromStr for VersionChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s == "git" {
+            return Ok(VersionChoice::Git);
+        }
+        if let Ok(kind) = s.parse::<BumpKind>() {
+            return Ok(VersionChoice::Increment(kind));
+        }
+        if let Some(version) = s.strip_prefix("history:") {
+            return if validate_version(version) {
+                Ok(VersionChoice::History(version.to_string()))
+            } else {
+                anyhow::bail!("无效的历史版本: {}", version)
+            };
+        }
+        s.parse::<Version>()
+            .map(|v| VersionChoice::Specified(v.to_string()))
+            .map_err(|_| anyhow::anyhow!("无效的版本参数: {}", s))
+    }
+}
+
+// 交互式选择版本，返回 None 表示放弃本次发布
+pub fn choose_version(history: &[String]) -> Result<Option<VersionChoice>> {
+    match show_version_menu()? {
+        ref s if s == "版本自增" => Ok(Some(VersionChoice::Increment(select_bump_kind()?))),
+        ref s if s == "指定版本" => {
+            print!("请输入版本号 (格式: v1.0.0 或 v1.0.0-rc.1): ");
+            io::stdout().flush()?;
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+
+            if let Ok(version) = input.parse::<Version>() {
+                println!("版本号有效: {}", version);
+                Ok(Some(VersionChoice::Specified(version.to_string())))
+            } else {
+                println!("无效的版本号格式");
+                Ok(None)
+            }
+        }
+        ref s if s == "历史版本" => {
+            let selected = select_history_version(history)?;
+            if let Some(selected) = &selected {
+                println!("选择的版本: {}", selected);
+            }
+            Ok(selected.map(VersionChoice::History))
+        }
+        ref s if s == "Git 标签" => Ok(Some(VersionChoice::Git)),
+        _ => unreachable!(),
+    }
+}
+
+// 在历史版本中查找与给定版本号相同的文件名
+pub fn find_history_version(history: &[String], version: &str) -> Option<String> {
+    let target = version.parse::<Version>().ok()?;
+    history
+        .iter()
+        .find(|s| s.parse::<Version>().ok().as_ref() == Some(&target))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bump(version: &str, kind: BumpKind) -> String {
+        version.parse::<Version>().unwrap().bump(kind).to_string()
+    }
+
+    fn version(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn build_metadata_is_ignored_in_comparison() {
+        assert_eq!(version("v1.2.0+build.5"), version("v1.2.0"));
+        assert!(version("v1.2.0-rc.1+build.9") < version("v1.2.0+build.1"));
+        assert!(version("v1.2.0+b") < version("v1.2.1+a"));
+    }
+
+    #[test]
+    fn finds_history_version_with_build_metadata() {
+        let history = ["v1.2.1.zip".to_string(), "v1.2.0+build.5.zip".to_string()];
+        assert_eq!(
+            find_history_version(&history, "v1.2.0").as_deref(),
+            Some("v1.2.0+build.5.zip")
+        );
+    }
+
+    #[test]
+    fn bumps_release() {
+        assert_eq!(bump("v1.3.2", BumpKind::Patch), "v1.3.3");
+        assert_eq!(bump("v1.3.2", BumpKind::Minor), "v1.4.0");
+        assert_eq!(bump("v1.3.2", BumpKind::Major), "v2.0.0");
+        assert_eq!(bump("v1.3.2", BumpKind::Prerelease), "v1.3.3-rc.1");
+    }
+
+    #[test]
+    fn prerelease_is_released() {
+        assert_eq!(bump("v1.4.0-rc.1", BumpKind::Patch), "v1.4.0");
+        assert_eq!(bump("v1.4.0-rc.1", BumpKind::Minor), "v1.4.0");
+        assert_eq!(bump("v2.0.0-rc.1", BumpKind::Major), "v2.0.0");
+        // 预发布版本不是对应级别的起点时照常升级
+        assert_eq!(bump("v1.4.1-rc.1", BumpKind::Minor), "v1.5.0");
+        assert_eq!(bump("v1.4.0-rc.1", BumpKind::Major), "v2.0.0");
+    }
+
+    #[test]
+    fn prerelease_increments() {
+        assert_eq!(bump("v1.4.0-rc.1", BumpKind::Prerelease), "v1.4.0-rc.2");
+        assert_eq!(bump("v1.4.0-rc.9", BumpKind::Prerelease), "v1.4.0-rc.10");
+        assert_eq!(bump("v1.4.0-beta", BumpKind::Prerelease), "v1.4.0-beta.1");
+        assert_eq!(bump("v1.4.0-3", BumpKind::Prerelease), "v1.4.0-4");
+    }
+
+    #[test]
+    fn build_metadata_is_dropped() {
+        assert_eq!(
+            bump("v1.4.0-rc.1+build.5", BumpKind::Prerelease),
+            "v1.4.0-rc.2"
+        );
+        assert_eq!(bump("v1.3.2+build.5", BumpKind::Patch), "v1.3.3");
+    }
 }
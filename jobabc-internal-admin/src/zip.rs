@@ -1,135 +1,279 @@
+use crate::archive::{self, ArchiveFormat};
 use crate::config::Sshconfig;
-use anyhow::Result;
+use crate::deployer::Deployer;
+use crate::dry_run;
+use crate::history;
+use crate::metadata::{self, DeployMetadata};
+use crate::progress;
+use crate::retention;
+use crate::ssh;
+use anyhow::{Context, Result};
 use ssh2::Session;
-use std::env;
 use std::fs;
-use std::io::{Read, Write};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
+// rollback 为 true 时按回滚更新部署记录，见 history::record_deploy
 pub fn compress_and_deploy(
+    deployer: &dyn Deployer,
     version: &str,
     sess: &Session,
     is_history: bool,
-    config: &Sshconfig,
+    rollback: bool,
 ) -> Result<()> {
-    let history_path = Path::new(&config.history_path);
-    let sftp = sess.sftp()?;
+    // 构建期间连接可能已经断开，上传前确认并在需要时重新连接；上传中断时也会替换为新连接
+    let mut sess = ssh::ensure_connected(sess, deployer.config())?;
+    // 解压、迁移等步骤也可能耗时较长，整个部署期间保持连接
+    let mut keepalive = ssh::keep_alive(&sess, &deployer.config().connection);
 
-    // 如果不是历史版本，需要先构建和压缩
+    // 如果不是历史版本，需要先压缩和上传
+    let mut build_metadata = None;
     if !is_history {
-        // 1. 压缩 dist 目录
-        println!("开始压缩 dist 目录...");
-        let home = env::var("HOME")?;
-        let project_dir = PathBuf::from(home).join("jobabc/jobabc-internal-admin");
-        let dist_dir = project_dir.join("dist");
-
-        if !dist_dir.exists() {
-            anyhow::bail!("dist 目录不存在");
+        let archive_file = deployer.package(version)?;
+        build_metadata = Some(DeployMetadata::collect(
+            deployer.config(),
+            version,
+            &archive_file,
+        )?);
+        deployer.upload(&mut sess, version, &archive_file)?;
+        // 上传中断重连后改为保持新的连接
+        keepalive = ssh::keep_alive(&sess, &deployer.config().connection);
+
+        // 清理本地文件
+        let project_dir = deployer.config().project_dir()?;
+        let checksum_file = project_dir.join(archive::checksum_file_name(version));
+        if deployer.config().options.dry_run {
+            dry_run::print("清理本地文件", archive_file.display());
+            dry_run::print("清理本地文件", checksum_file.display());
+            dry_run::print("清理本地文件", project_dir.join("dist").display());
+        } else {
+            fs::remove_file(&archive_file)?;
+            fs::remove_file(&checksum_file)?;
+            fs::remove_dir_all(project_dir.join("dist"))?;
         }
+    } else {
+        // 重新部署历史版本前确认压缩包没有损坏
+        verify_checksum(&sess, deployer.config(), version, true)?;
+    }
+
+    deployer.activate(&sess, version, is_history)?;
+    deployer.post_deploy(&sess)?;
+    history::record_deploy(deployer.config(), &sess, version, rollback)?;
+    metadata::save(&sess, deployer.config(), version, build_metadata)?;
+
+    // 配置了 retention.auto 时按保留策略自动清理旧版本，失败不影响本次部署
+    if deployer.config().retention.auto
+        && let Err(e) = retention::prune(&sess, deployer.config(), true)
+    {
+        eprintln!("警告: 自动清理历史版本失败: {}", e);
+    }
+
+    drop(keepalive);
+    if deployer.config().options.dry_run {
+        println!("演练完成，未做任何修改");
+    } else {
+        println!("部署完成！");
+    }
+    Ok(())
+}
 
-        // 创建临时 zip 文件
-        let temp_zip = project_dir.join("temp.zip");
-        let zip_file = project_dir.join(format!("{}.zip", version));
+pub fn package(config: &Sshconfig, version: &str, dirs: &[&str]) -> Result<PathBuf> {
+    let project_dir = config.project_dir()?;
+    let format = ArchiveFormat::from_file_name(version).unwrap_or(config.archive_format);
 
-        // 使用 zip 命令压缩
-        let status = Command::new("zip")
-            .arg("-r")
-            .arg(&temp_zip)
-            .arg("dist")
-            .current_dir(&project_dir)
-            .status()?;
+    // 先写入临时文件，完成后再重命名为版本号
+    let temp_file = project_dir.join(format!("{}.tmp", version));
+    let archive_file = project_dir.join(version);
 
-        if !status.success() {
-            anyhow::bail!("压缩失败");
+    // 演练模式下还没有构建产物，只打印打包计划
+    if config.options.dry_run {
+        dry_run::print(
+            "压缩",
+            format!(
+                "{} ({}) -> {}",
+                dirs.join(" "),
+                format.extension(),
+                archive_file.display()
+            ),
+        );
+        return Ok(archive_file);
+    }
+
+    for dir in dirs {
+        if !project_dir.join(dir).exists() {
+            anyhow::bail!("{} 目录不存在", dir);
         }
+    }
 
-        // 重命名为版本号
-        fs::rename(&temp_zip, &zip_file)?;
+    progress::run(format!("压缩 {}", dirs.join("、")), |_| {
+        archive::create(&project_dir, dirs, &temp_file, format).with_context(|| "压缩失败")
+    })?;
 
-        // 读取文件内容
-        let zip_content = fs::read(&zip_file)?;
+    // 重命名为版本号，并生成校验文件
+    fs::rename(&temp_file, &archive_file)?;
+    archive::write_checksum(&archive_file)?;
 
-        // 上传到历史版本目录
-        println!("开始上传到历史版本目录...");
-        let remote_path = history_path.join(version);
-        let mut remote_file = sftp.create(&remote_path)?;
-        remote_file.write_all(&zip_content)?;
-        drop(remote_file);
+    Ok(archive_file)
+}
 
-        // 清理本地文件
-        fs::remove_file(&zip_file)?;
-        fs::remove_dir_all(&dist_dir)?;
+pub fn upload(
+    sess: &mut Session,
+    config: &Sshconfig,
+    version: &str,
+    archive_file: &Path,
+) -> Result<()> {
+    let remote_path = Path::new(&config.history_path).join(version);
+    let checksum_name = archive::checksum_file_name(version);
+    if config.options.dry_run {
+        dry_run::print(
+            "SFTP 上传",
+            format!("{} -> {}", archive_file.display(), remote_path.display()),
+        );
+        dry_run::print(
+            "校验压缩包",
+            format!("sha256sum {}.part", remote_path.display()),
+        );
+        return Ok(());
     }
 
-    // 2. 部署到生产目录
-    println!("开始部署到生产目录...");
-    let deploy_path = Path::new(&config.output_path);
+    // 先上传校验文件，压缩包校验通过后才会出现在历史版本目录中
+    let checksum = fs::read_to_string(archive_file.with_file_name(&checksum_name))?;
+    let sha256 = checksum.split_whitespace().next().unwrap_or_default();
+    let sftp = sess.sftp()?;
+    let mut remote_checksum = sftp.create(&Path::new(&config.history_path).join(&checksum_name))?;
+    remote_checksum.write_all(checksum.as_bytes())?;
+    drop(remote_checksum);
+
+    ssh::upload_file(sess, config, archive_file, &remote_path, sha256).with_context(|| {
+        format!(
+            "上传 {} 失败，重新部署同一版本会从断点继续上传",
+            remote_path.display()
+        )
+    })
+}
 
-    // 检查部署目录是否存在
-    let mut channel = sess.channel_session()?;
-    channel.exec(&format!("cd / && [ -d {} ]", deploy_path.display()))?;
+// 在服务器上用 sha256sum 校验历史版本目录中的压缩包。
+// 早期版本没有校验文件，allow_missing 为 true 时只给出提示
+pub fn verify_checksum(
+    sess: &Session,
+    config: &Sshconfig,
+    version: &str,
+    allow_missing: bool,
+) -> Result<()> {
+    let checksum_name = archive::checksum_file_name(version);
+    let exists = ssh::execute(
+        sess,
+        &format!("[ -f {}/{} ]", config.history_path, checksum_name),
+    )?
+    .success();
 
-    // 读取命令输出
-    let mut output = String::new();
-    channel.read_to_string(&mut output)?;
+    if !exists && !config.options.dry_run {
+        if allow_missing {
+            println!("{} 没有校验文件，跳过校验", version);
+            return Ok(());
+        }
+        anyhow::bail!("缺少校验文件 {}", checksum_name);
+    }
 
-    let exit_status = channel.exit_status()?;
-    channel.wait_close()?;
+    ssh::run_step(
+        sess,
+        config,
+        &format!("校验 {}", version),
+        &format!(
+            "cd {} && sha256sum -c --quiet {}",
+            config.history_path, checksum_name
+        ),
+    )?;
 
-    // 如果目录不存在，创建它
-    if exit_status != 0 {
-        println!("创建部署目录...");
-        let mut channel = sess.channel_session()?;
-        channel.exec(&format!("mkdir -p {}", deploy_path.display()))?;
+    Ok(())
+}
+
+// 版本对应的发布目录名，去掉压缩包后缀
+pub fn release_name(version: &str) -> &str {
+    archive::strip_extension(version)
+}
 
-        // 读取命令输出
-        let mut output = String::new();
-        channel.read_to_string(&mut output)?;
+// current 软链接指向的发布目录名，current 不存在时返回 None
+fn live_release(sess: &Session, config: &Sshconfig) -> Result<Option<String>> {
+    let current = Path::new(&config.output_path).join("current");
+    let output = ssh::execute(
+        sess,
+        &format!("readlink {}", ssh::quote(&current.display().to_string())),
+    )?;
+    Ok(output.success().then(|| {
+        let target = output.stdout.trim();
+        target
+            .strip_prefix("releases/")
+            .unwrap_or(target)
+            .to_string()
+    }))
+}
 
-        channel.wait_close()?;
+// 重新构建的版本会替换整个发布目录，替换期间 current 会指向不存在的目录，
+// 因此不允许覆盖 current 正在使用的版本
+pub fn check_not_live(sess: &Session, config: &Sshconfig, version: &str) -> Result<()> {
+    let release = release_name(version);
+    if live_release(sess, config)?.as_deref() == Some(release) {
+        anyhow::bail!(
+            "{} 是当前正在使用的版本，不能重新构建覆盖，请使用新的版本号",
+            release
+        );
     }
+    Ok(())
+}
+
+// 解压到 output_path/releases/<版本>/，成功后再切换 current 软链接
+pub fn activate(
+    sess: &Session,
+    config: &Sshconfig,
+    version: &str,
+    dirs: &[&str],
+    is_history: bool,
+) -> Result<()> {
+    let history_path = Path::new(&config.history_path);
+    let deploy_path = Path::new(&config.output_path);
+    let release = release_name(version);
+    let release_path = deploy_path.join("releases").join(release);
+    let format = ArchiveFormat::from_file_name(version).unwrap_or_default();
+
+    // 检查发布目录是否存在
+    let release_exists =
+        ssh::execute(sess, &format!("[ -d {} ]", release_path.display()))?.success();
+
+    // 历史版本已经解压过时直接切换，否则先解压到临时目录再整体移动。
+    // 被替换的发布目录不会是 current 正在使用的，见 check_not_live
+    if is_history && release_exists {
+        println!("发布目录已存在，直接切换到 {}", release);
+    } else {
+        let temp_path = deploy_path
+            .join("releases")
+            .join(format!(".{}.tmp", release));
+        ssh::run_step(
+            sess,
+            config,
+            &format!("解压到 releases/{}", release),
+            &format!(
+                "rm -rf {tmp} && mkdir -p {tmp} && cd {tmp} && {extract} && chmod -R 755 {dirs} && rm -rf {release} && mv {tmp} {release}",
+                tmp = temp_path.display(),
+                extract =
+                    format.extract_command(&format!("{}/{}", history_path.display(), version)),
+                dirs = dirs.join(" "),
+                release = release_path.display(),
+            ),
+        )?;
+    }
+
+    // 先创建临时软链接再 mv 覆盖，保证切换是原子的
+    ssh::run_step(
+        sess,
+        config,
+        &format!("切换 current 到 {}", release),
+        &format!(
+            "cd {} && ln -sfn releases/{} current.tmp && mv -Tf current.tmp current",
+            deploy_path.display(),
+            release
+        ),
+    )?;
 
-    // 从历史目录复制文件到部署目录
-    println!("从历史版本复制文件...");
-    let mut channel = sess.channel_session()?;
-    channel.exec(&format!(
-        "cp {}/{} {}/{}",
-        history_path.display(),
-        version,
-        deploy_path.display(),
-        version
-    ))?;
-
-    // 读取命令输出
-    let mut output = String::new();
-    channel.read_to_string(&mut output)?;
-    channel.wait_close()?;
-
-    // 解压文件
-    let mut channel = sess.channel_session()?;
-    channel.exec(&format!(
-        "cd {} && unzip -o {} && rm {} && chmod -R 755 dist",
-        deploy_path.display(),
-        version,
-        version
-    ))?;
-
-    // 读取命令输出
-    let mut output = String::new();
-    channel.read_to_string(&mut output)?;
-    channel.wait_close()?;
-
-    // 3. 重启 nginx
-    println!("重启 nginx...");
-    let mut channel = sess.channel_session()?;
-    channel.exec("nginx -s reload")?;
-
-    // 读取命令输出
-    let mut output = String::new();
-    channel.read_to_string(&mut output)?;
-    channel.wait_close()?;
-
-    println!("部署完成！");
     Ok(())
 }
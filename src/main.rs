@@ -1,9 +1,166 @@
+use clap::{Parser, Subcommand};
 use dialoguer::{Select, theme::ColorfulTheme};
-use jobabc_internal_admin;
-use jobabc_internal_next;
+use jobabc_internal_admin::config::RunOptions;
+use jobabc_internal_admin::version::VersionChoice;
+use manifest::{Manifest, Project, ProjectKind};
+use std::str::FromStr;
 
-fn main() {
-    let projects = vec!["jobabc-internal-admin", "jobabc-internal-next", "退出"];
+mod manifest;
+
+#[derive(Parser)]
+#[command(
+    name = "deploy_tools",
+    about = "项目打包发布工具，不带参数时进入交互菜单"
+)]
+struct Cli {
+    /// 演练模式：只打印部署计划，不执行构建、上传和远程修改
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// 严格校验主机密钥：不在 known_hosts 中的服务器直接报错，不提示确认
+    #[arg(long, global = true)]
+    strict_host_key_checking: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// 打包并部署项目
+    Deploy {
+        /// 项目名称，例如 jobabc-internal-admin
+        #[arg(long)]
+        project: String,
+        /// 部署环境，例如 dev、prod
+        #[arg(long)]
+        env: String,
+        /// 版本：bump/patch、minor、major、prerelease（自增）、git（按 git 标签和提交计算）、v1.2.3 或 v1.4.0-rc.1（指定版本）、history:v1.2.0（历史版本）
+        #[arg(long, default_value = "bump", value_parser = VersionChoice::from_str)]
+        version: VersionChoice,
+    },
+    /// 查看历史版本
+    History {
+        /// 项目名称，例如 jobabc-internal-admin
+        #[arg(long)]
+        project: String,
+        /// 部署环境，例如 dev、prod
+        #[arg(long)]
+        env: String,
+        /// 显示的版本数量
+        #[arg(long, default_value_t = jobabc_internal_admin::history::DEFAULT_DISPLAY_LIMIT)]
+        limit: usize,
+    },
+    /// 按 retention 配置清理旧的历史版本
+    Prune {
+        /// 项目名称，例如 jobabc-internal-admin
+        #[arg(long)]
+        project: String,
+        /// 部署环境，例如 dev、prod
+        #[arg(long)]
+        env: String,
+        /// 跳过确认
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// 回滚到上一个部署的版本
+    Rollback {
+        /// 项目名称，例如 jobabc-internal-admin
+        #[arg(long)]
+        project: String,
+        /// 部署环境，例如 dev、prod
+        #[arg(long)]
+        env: String,
+        /// 跳过确认
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let manifest = Manifest::load("deploy.toml")?;
+    let options = RunOptions {
+        dry_run: cli.dry_run,
+        strict_host_key_checking: cli.strict_host_key_checking,
+    };
+    if options.dry_run {
+        println!("演练模式：只打印部署计划，不会修改任何内容");
+    }
+
+    match cli.command {
+        Some(Commands::Deploy {
+            project,
+            env,
+            version,
+        }) => {
+            println!("正在打包 {} {} 环境...", project, env);
+            deploy(manifest.find(&project)?, &env, Some(version), options)
+        }
+        Some(Commands::History {
+            project,
+            env,
+            limit,
+        }) => {
+            let project = manifest.find(&project)?;
+            project.check_env(&env)?;
+            jobabc_internal_admin::config::connect_with_limit(&project.path, &env, limit, options)?;
+            Ok(())
+        }
+        Some(Commands::Prune { project, env, yes }) => {
+            let project = manifest.find(&project)?;
+            project.check_env(&env)?;
+            jobabc_internal_admin::config::prune(&project.path, &env, yes, options)
+        }
+        Some(Commands::Rollback { project, env, yes }) => {
+            println!("正在回滚 {} {} 环境...", project, env);
+            rollback(manifest.find(&project)?, &env, yes, options)
+        }
+        None => {
+            menu(&manifest, options);
+            Ok(())
+        }
+    }
+}
+
+// 根据项目类型分发到对应的部署流程，菜单和命令行共用
+fn deploy(
+    project: &Project,
+    env: &str,
+    choice: Option<VersionChoice>,
+    options: RunOptions,
+) -> anyhow::Result<()> {
+    project.check_env(env)?;
+
+    match project.kind {
+        ProjectKind::Static => {
+            jobabc_internal_admin::config::read_config(&project.path, env, choice, options)
+        }
+        ProjectKind::Node => jobabc_internal_next::build::main(&project.path, env, choice, options),
+    }
+}
+
+fn rollback(
+    project: &Project,
+    env: &str,
+    assume_yes: bool,
+    options: RunOptions,
+) -> anyhow::Result<()> {
+    project.check_env(env)?;
+
+    match project.kind {
+        ProjectKind::Static => {
+            jobabc_internal_admin::config::rollback(&project.path, env, assume_yes, options)
+        }
+        ProjectKind::Node => {
+            jobabc_internal_next::build::rollback(&project.path, env, assume_yes, options)
+        }
+    }
+}
+
+fn menu(manifest: &Manifest, options: RunOptions) {
+    let mut projects: Vec<&str> = manifest.projects.iter().map(|p| p.name.as_str()).collect();
+    projects.push("退出");
 
     loop {
         let selection = Select::with_theme(&ColorfulTheme::default())
@@ -13,75 +170,46 @@ fn main() {
             .interact()
             .unwrap();
 
-        match selection {
-            0 => {
-                println!("正在运行 jobabc-internal-admin...");
-                let actions = vec!["打包 dev", "打包 prod", "返回主菜单"];
-
-                let action = Select::with_theme(&ColorfulTheme::default())
-                    .with_prompt("请选择操作")
-                    .items(&actions)
-                    .default(0)
-                    .interact()
-                    .unwrap();
-
-                // 根据 action 选择打包环境
-                match action {
-                    0 => {
-                        let env = String::from("dev");
-                        println!("正在打包 dev 环境...");
-                        match jobabc_internal_admin::config::read_config(&env) {
-                            Ok(()) => println!("配置加载成功"),
-                            Err(e) => println!("配置加载失败: {}", e),
-                        }
-                        break;
-                    }
-                    1 => {
-                        let env = String::from("prod");
-                        println!("正在打包 prod 环境... {}", env);
-                        match jobabc_internal_admin::config::read_config(&env) {
-                            Ok(()) => println!("配置加载成功"),
-                            Err(e) => println!("配置加载失败: {}", e),
-                        }
-                        break;
-                    }
-                    2 => {
-                        println!("返回主菜单");
-                        continue;
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            1 => {
-                println!("正在运行 jobabc-internal-next...");
-                let actions = vec!["打包 dev", "打包 prod", "返回主菜单"];
-
-                let action = Select::with_theme(&ColorfulTheme::default())
-                    .with_prompt("请选择操作")
-                    .items(&actions)
-                    .default(0)
-                    .interact()
-                    .unwrap();
-
-                // 根据 action 选择打包环境
-                match action {
-                    0 => {
-                        let env = String::from("dev");
-                        println!("正在打包 dev 环境...");
-                        match jobabc_internal_next::build::main(&env) {
-                            Ok(()) => println!("打包成功"),
-                            Err(e) => println!("打包失败: {}", e),
-                        }
-                        break;
-                    }
-                    _ => unreachable!(),
-                }
+        if selection == manifest.projects.len() {
+            println!("退出程序");
+            break;
+        }
+
+        let project = &manifest.projects[selection];
+        println!("正在运行 {}...", project.name);
+        let envs = &project.environments;
+        let mut actions: Vec<String> = envs.iter().map(|env| format!("打包 {}", env)).collect();
+        actions.extend(envs.iter().map(|env| format!("回滚 {}", env)));
+        actions.push("返回主菜单".to_string());
+
+        let action = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("请选择操作")
+            .items(&actions)
+            .default(0)
+            .interact()
+            .unwrap();
+
+        if action == envs.len() * 2 {
+            println!("返回主菜单");
+            continue;
+        }
+
+        // 根据 action 选择打包或回滚的环境
+        if action < envs.len() {
+            let env = &envs[action];
+            println!("正在打包 {} 环境...", env);
+            match deploy(project, env, None, options) {
+                Ok(()) => println!("打包成功"),
+                Err(e) => println!("打包失败: {}", e),
             }
-            2 => {
-                println!("退出程序");
-                break;
+        } else {
+            let env = &envs[action - envs.len()];
+            println!("正在回滚 {} 环境...", env);
+            match rollback(project, env, false, options) {
+                Ok(()) => println!("回滚完成"),
+                Err(e) => println!("回滚失败: {}", e),
             }
-            _ => unreachable!(),
         }
+        break;
     }
 }
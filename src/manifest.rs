@@ -0,0 +1,158 @@
+use jobabc_internal_admin::config;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+// 项目类型，决定使用哪一套部署流程
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectKind {
+    Static,
+    Node,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Project {
+    pub name: String,
+    pub kind: ProjectKind,
+    pub path: String,
+    pub environments: Vec<String>,
+}
+
+impl Project {
+    pub fn check_env(&self, env: &str) -> anyhow::Result<()> {
+        if !self.environments.iter().any(|e| e == env) {
+            anyhow::bail!("项目 {} 没有 {} 环境", self.name, env);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub projects: Vec<Project>,
+}
+
+impl Manifest {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("读取项目清单 {} 失败: {}", path, e))?;
+        let manifest: Manifest = toml::from_str(&content)?;
+        manifest.check_paths()?;
+        Ok(manifest)
+    }
+
+    // 同一环境的两个项目使用相同的 history_path 或 output_path 时，会互相覆盖压缩包、
+    // 部署记录和 current 软链接，清理时还会删除对方的版本，因此直接拒绝运行
+    fn check_paths(&self) -> anyhow::Result<()> {
+        let configs = self
+            .projects
+            .iter()
+            .map(|project| {
+                config::read_env_config(&project.path)
+                    .map(|env_config| (project, env_config))
+                    .map_err(|e| anyhow::anyhow!("读取项目 {} 的配置失败: {}", project.name, e))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut entries = Vec::new();
+        for (project, env_config) in &configs {
+            for env in &project.environments {
+                if let Some(config) = env_config.get(env) {
+                    entries.push(PathEntry {
+                        project: &project.name,
+                        env,
+                        history_path: &config.history_path,
+                        output_path: &config.output_path,
+                    });
+                }
+            }
+        }
+        check_path_conflicts(&entries)
+    }
+
+    pub fn find(&self, name: &str) -> anyhow::Result<&Project> {
+        self.projects
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| anyhow::anyhow!("未知项目: {}", name))
+    }
+}
+
+struct PathEntry<'a> {
+    project: &'a str,
+    env: &'a str,
+    history_path: &'a str,
+    output_path: &'a str,
+}
+
+fn check_path_conflicts(entries: &[PathEntry]) -> anyhow::Result<()> {
+    let mut seen: HashMap<(&str, &str, &str), &str> = HashMap::new();
+    for entry in entries {
+        for (kind, path) in [
+            ("history_path", entry.history_path),
+            ("output_path", entry.output_path),
+        ] {
+            let path = path.trim_end_matches('/');
+            if let Some(other) = seen.insert((entry.env, kind, path), entry.project) {
+                anyhow::bail!(
+                    "项目 {} 和 {} 的 {} 环境使用了相同的 {}: {}，请修改 config.json",
+                    other,
+                    entry.project,
+                    entry.env,
+                    kind,
+                    path
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry<'a>(
+        project: &'a str,
+        env: &'a str,
+        history: &'a str,
+        output: &'a str,
+    ) -> PathEntry<'a> {
+        PathEntry {
+            project,
+            env,
+            history_path: history,
+            output_path: output,
+        }
+    }
+
+    #[test]
+    fn distinct_paths_are_allowed() {
+        let entries = [
+            entry("admin", "prod", "/srv/web-history", "/srv/admin"),
+            entry("next", "prod", "/srv/backend_history", "/srv/backstage"),
+            // 不同环境在不同服务器上，路径相同不冲突
+            entry("next", "dev", "/srv/web-history", "/srv/admin"),
+        ];
+        assert!(check_path_conflicts(&entries).is_ok());
+    }
+
+    #[test]
+    fn shared_history_path_is_rejected() {
+        let entries = [
+            entry("admin", "prod", "/srv/web-history", "/srv/admin"),
+            entry("next", "prod", "/srv/web-history/", "/srv/backstage"),
+        ];
+        assert!(check_path_conflicts(&entries).is_err());
+    }
+
+    #[test]
+    fn shared_output_path_is_rejected() {
+        let entries = [
+            entry("admin", "dev", "/srv/web-history", "/srv/admin"),
+            entry("next", "dev", "/srv/backend_history", "/srv/admin"),
+        ];
+        assert!(check_path_conflicts(&entries).is_err());
+    }
+}